@@ -0,0 +1,42 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+// Implements diysh::commands::typed::FromArgs for a struct with named fields, each field becomes a positional argument
+#[proc_macro_derive(FromArgs)]
+pub fn derive_from_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return syn::Error::new_spanned(name, "FromArgs can only be derived for structs with named fields").to_compile_error().into()
+        },
+        _ => return syn::Error::new_spanned(name, "FromArgs can only be derived for structs").to_compile_error().into()
+    };
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let indexes: Vec<_> = (0..fields.len()).collect();
+    let names: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap().to_string()).collect();
+
+    quote! {
+        impl #impl_generics ::diysh::commands::typed::FromArgs for #name #type_generics #where_clause {
+            fn arg_types() -> ::std::vec::Vec<::diysh::commands::argument::ArgType> {
+                ::std::vec![#(<#types as ::diysh::commands::typed::FromArg>::arg_type()),*]
+            }
+
+            fn arg_names() -> ::std::vec::Vec<&'static str> {
+                ::std::vec![#(#names),*]
+            }
+
+            fn from_args(args: &[::diysh::commands::argument::EvaluatedArg]) -> ::std::option::Option<Self> {
+                ::std::option::Option::Some(Self {
+                    #(#idents: <#types as ::diysh::commands::typed::FromArg>::from_arg(args.get(#indexes)?)?),*
+                })
+            }
+        }
+    }.into()
+}
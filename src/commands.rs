@@ -1,3 +1,7 @@
 pub mod argument;
 pub mod definition;
-mod instance;
\ No newline at end of file
+pub mod external;
+pub mod suggest;
+pub mod typed;
+pub mod validator;
+pub(crate) mod instance;
\ No newline at end of file
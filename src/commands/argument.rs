@@ -1,13 +1,142 @@
-use std::fmt::Display;
+use std::{any::Any, fmt::{Debug, Display}, net::IpAddr, ops::Deref, path::PathBuf, sync::{Arc, OnceLock}, time::Duration};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
 
 use crate::{error::CommandError, inout::read::ArgToken};
 
+use super::{suggest, validator::Validator};
+
 #[derive(Debug)]
 pub enum ArgType {
     Str,
     Int,
     Float,
-    Bool
+    Bool,
+    Long,
+    ULong,
+    Double,
+    Char,
+    Path,
+    Enum(Vec<&'static str>),
+    Duration,
+    Bytes,
+    IpAddr,
+    DateTime,
+    Custom(CustomType),
+    List(Box<ArgType>, char),
+    Tuple(Vec<ArgType>, char)
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgDefinition {
+    name: &'static str,
+    arg_type: ArgType,
+    description: &'static str,
+    optional: bool,
+    validators: Vec<Validator>
+}
+
+impl ArgDefinition {
+    pub fn new(arg_type: ArgType) -> Self {
+        Self { name: "", arg_type, description: "", optional: false, validators: vec![] }
+    }
+
+    pub fn build(&self) -> ArgDefinition {
+        self.clone()
+    }
+
+    pub fn set_name(&mut self, name: &'static str) -> &mut Self {
+        self.name = name;
+
+        self
+    }
+
+    pub fn set_description(&mut self, description: &'static str) -> &mut Self {
+        self.description = description;
+
+        self
+    }
+
+    pub fn set_optional(&mut self, optional: bool) -> &mut Self {
+        self.optional = optional;
+
+        self
+    }
+
+    pub fn add_validator(&mut self, validator: Validator) -> &mut Self {
+        self.validators.push(validator);
+
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn arg_type(&self) -> &ArgType {
+        &self.arg_type
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    // The name of the argument, or its position when it's unnamed
+    pub fn label(&self, index: usize) -> String {
+        if self.name.is_empty() { format!("#{}", index + 1) } else { self.name.to_string() }
+    }
+
+    // How the argument is shown in usage lines, like <len:Int> or [command:Str] when it's optional
+    pub fn usage(&self) -> String {
+        let inner = if self.name.is_empty() { self.arg_type.to_string() } else { format!("{}:{}", self.name, self.arg_type) };
+
+        if self.optional { format!("[{}]", inner) } else { format!("<{}>", inner) }
+    }
+
+    pub fn validators(&self) -> &Vec<Validator> {
+        &self.validators
+    }
+}
+
+// Implement this trait to use your own types as command arguments, then add them with ArgType::custom::<T>()
+pub trait CustomArgType: Clone + Send + Sync + 'static {
+    // The name shown in the help page and error messages
+    fn type_name() -> String;
+
+    // Converts the token into a value, returning None makes the command fail with a MismatchArgument
+    fn parse(token: &ArgToken) -> Option<Self>;
+
+    fn completions() -> Vec<String> {
+        vec![]
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CustomType {
+    type_name: fn() -> String,
+    parse: fn(&ArgToken) -> Option<Arc<dyn Any + Send + Sync>>,
+    completions: fn() -> Vec<String>
+}
+
+impl CustomType {
+    pub fn type_name(&self) -> String {
+        (self.type_name)()
+    }
+
+    pub fn completions(&self) -> Vec<String> {
+        (self.completions)()
+    }
+}
+
+impl Debug for CustomType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CustomType({})", self.type_name())
+    }
 }
 
 impl Display for ArgType {
@@ -16,7 +145,20 @@ impl Display for ArgType {
             ArgType::Str => write!(f, "Str"),
             ArgType::Int => write!(f, "Int"),
             ArgType::Float => write!(f, "Float"),
-            ArgType::Bool => write!(f, "Bool")
+            ArgType::Bool => write!(f, "Bool"),
+            ArgType::Long => write!(f, "Long"),
+            ArgType::ULong => write!(f, "ULong"),
+            ArgType::Double => write!(f, "Double"),
+            ArgType::Char => write!(f, "Char"),
+            ArgType::Path => write!(f, "Path"),
+            ArgType::Enum(choices) => write!(f, "Enum({})", choices.join("|")),
+            ArgType::Duration => write!(f, "Duration"),
+            ArgType::Bytes => write!(f, "Bytes"),
+            ArgType::IpAddr => write!(f, "IpAddr"),
+            ArgType::DateTime => write!(f, "DateTime"),
+            ArgType::Custom(custom) => write!(f, "{}", custom.type_name()),
+            ArgType::List(inner, _) => write!(f, "List<{}>", inner),
+            ArgType::Tuple(inners, separator) => write!(f, "({})", inners.iter().map(|inner| inner.to_string()).collect::<Vec<String>>().join(&separator.to_string()))
         }
     }
 }
@@ -28,6 +170,19 @@ impl Clone for ArgType {
             Self::Int => Self::Int,
             Self::Float => Self::Float,
             Self::Bool => Self::Bool,
+            Self::Long => Self::Long,
+            Self::ULong => Self::ULong,
+            Self::Double => Self::Double,
+            Self::Char => Self::Char,
+            Self::Path => Self::Path,
+            Self::Enum(choices) => Self::Enum(choices.clone()),
+            Self::Duration => Self::Duration,
+            Self::Bytes => Self::Bytes,
+            Self::IpAddr => Self::IpAddr,
+            Self::DateTime => Self::DateTime,
+            Self::Custom(custom) => Self::Custom(*custom),
+            Self::List(inner, separator) => Self::List(inner.clone(), *separator),
+            Self::Tuple(inners, separator) => Self::Tuple(inners.clone(), *separator),
         }
     }
 }
@@ -37,37 +192,217 @@ pub enum EvaluatedArg {
     Str(String),
     Int(i32),
     Float(f32),
-    Bool(bool)
+    Bool(bool),
+    Long(i64),
+    ULong(u64),
+    Double(f64),
+    Char(char),
+    Path(PathBuf),
+    Enum(String),
+    Duration(Duration),
+    Bytes(u64),
+    IpAddr(IpAddr),
+    DateTime(NaiveDateTime),
+    Custom(Arc<dyn Any + Send + Sync>),
+    List(Vec<EvaluatedArg>),
+    Tuple(Vec<EvaluatedArg>)
+}
+
+// The evaluated arguments given to a callback, it can be indexed like a Vec<EvaluatedArg> or searched by argument name
+#[derive(Debug, Clone)]
+pub struct ArgList {
+    values: Vec<EvaluatedArg>,
+    names: Vec<&'static str>
+}
+
+impl ArgList {
+    pub fn new(values: Vec<EvaluatedArg>, names: Vec<&'static str>) -> Self {
+        Self { values, names }
+    }
+
+    // Gets the value of the argument with the given name, None if there's no such argument or it was omitted
+    pub fn named(&self, name: &str) -> Option<&EvaluatedArg> {
+        let index = self.names.iter().position(|arg_name| *arg_name == name)?;
+
+        self.values.get(index)
+    }
+}
+
+impl Deref for ArgList {
+    type Target = Vec<EvaluatedArg>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+// The value as the command got it, custom values can't be shown so they're written as <custom>
+impl Display for EvaluatedArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvaluatedArg::Str(value) | EvaluatedArg::Enum(value) => write!(f, "{}", value),
+            EvaluatedArg::Int(value) => write!(f, "{}", value),
+            EvaluatedArg::Float(value) => write!(f, "{}", value),
+            EvaluatedArg::Bool(value) => write!(f, "{}", value),
+            EvaluatedArg::Long(value) => write!(f, "{}", value),
+            EvaluatedArg::ULong(value) | EvaluatedArg::Bytes(value) => write!(f, "{}", value),
+            EvaluatedArg::Double(value) => write!(f, "{}", value),
+            EvaluatedArg::Char(value) => write!(f, "{}", value),
+            EvaluatedArg::Path(value) => write!(f, "{}", value.display()),
+            EvaluatedArg::Duration(value) => write!(f, "{:?}", value),
+            EvaluatedArg::IpAddr(value) => write!(f, "{}", value),
+            EvaluatedArg::DateTime(value) => write!(f, "{}", value),
+            EvaluatedArg::Custom(_) => write!(f, "<custom>"),
+            EvaluatedArg::List(values) | EvaluatedArg::Tuple(values) => write!(f, "{}", values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(","))
+        }
+    }
 }
 
 impl Clone for EvaluatedArg {
     fn clone(&self) -> Self {
         match self {
             Self::Str(arg0) => Self::Str(arg0.clone()),
-            Self::Int(arg0) => Self::Int(arg0.clone()),
-            Self::Float(arg0) => Self::Float(arg0.clone()),
-            Self::Bool(arg0) => Self::Bool(arg0.clone()),
+            Self::Int(arg0) => Self::Int(*arg0),
+            Self::Float(arg0) => Self::Float(*arg0),
+            Self::Bool(arg0) => Self::Bool(*arg0),
+            Self::Long(arg0) => Self::Long(*arg0),
+            Self::ULong(arg0) => Self::ULong(*arg0),
+            Self::Double(arg0) => Self::Double(*arg0),
+            Self::Char(arg0) => Self::Char(*arg0),
+            Self::Path(arg0) => Self::Path(arg0.clone()),
+            Self::Enum(arg0) => Self::Enum(arg0.clone()),
+            Self::Duration(arg0) => Self::Duration(*arg0),
+            Self::Bytes(arg0) => Self::Bytes(*arg0),
+            Self::IpAddr(arg0) => Self::IpAddr(*arg0),
+            Self::DateTime(arg0) => Self::DateTime(*arg0),
+            Self::Custom(arg0) => Self::Custom(arg0.clone()),
+            Self::List(arg0) => Self::List(arg0.clone()),
+            Self::Tuple(arg0) => Self::Tuple(arg0.clone()),
         }
     }
 }
 
 impl ArgType {
-    pub fn evaluate(&self, value: &ArgToken) -> Result<EvaluatedArg, CommandError> {
-        let value = value.to_string();
+    pub fn custom<T: CustomArgType>() -> Self {
+        Self::Custom(CustomType {
+            type_name: T::type_name,
+            parse: |token| T::parse(token).map(|value| Arc::new(value) as Arc<dyn Any + Send + Sync>),
+            completions: T::completions
+        })
+    }
+
+    pub fn list(inner: ArgType, separator: char) -> Self {
+        Self::List(Box::new(inner), separator)
+    }
+
+    pub fn tuple(inners: Vec<ArgType>, separator: char) -> Self {
+        Self::Tuple(inners, separator)
+    }
+
+    // The values that can be suggested for this argument, empty if any value goes
+    pub fn completions(&self) -> Vec<String> {
+        match self {
+            Self::Bool => vec!["true".to_string(), "false".to_string()],
+            Self::Enum(choices) => choices.iter().map(|choice| choice.to_string()).collect(),
+            Self::Custom(custom) => custom.completions(),
+            _ => vec![]
+        }
+    }
+
+    // Converts the token into a value of this type, mismatches suggest the closest completions
+    pub fn evaluate(&self, token: &ArgToken) -> Result<EvaluatedArg, CommandError> {
+        match self.evaluate_value(token) {
+            Err(CommandError::MismatchArgument { value, expected, argument, suggestions, span }) if suggestions.is_empty() => {
+                let completions = self.completions();
+                let suggestions = suggest::suggestions(&value, completions.iter().map(|completion| completion.as_str()));
+
+                Err(CommandError::MismatchArgument { value, expected, argument, suggestions, span })
+            },
+            result => result
+        }
+    }
+
+    fn evaluate_value(&self, token: &ArgToken) -> Result<EvaluatedArg, CommandError> {
+        let value = token.to_string();
         match self {
             Self::Str => Ok(EvaluatedArg::Str(value)),
             Self::Int => match value.parse::<i32>() {
                 Ok(int) => Ok(EvaluatedArg::Int(int)),
-                Err(_) => Err(CommandError::MismatchArgument(value.to_string(), ArgType::Int))
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::Int, token.span()))
             },
             Self::Float => match value.parse::<f32>() {
                 Ok(float) => Ok(EvaluatedArg::Float(float)),
-                Err(_) => Err(CommandError::MismatchArgument(value.to_string(), ArgType::Float))
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::Float, token.span()))
             },
             Self::Bool => match value.parse::<bool>() { 
                 Ok(b) => Ok(EvaluatedArg::Bool(b)),
-                Err(_) => Err(CommandError::MismatchArgument(value.to_string(), ArgType::Bool))
-            }        
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::Bool, token.span()))
+            },
+            Self::Long => match value.parse::<i64>() {
+                Ok(long) => Ok(EvaluatedArg::Long(long)),
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::Long, token.span()))
+            },
+            Self::ULong => match value.parse::<u64>() {
+                Ok(ulong) => Ok(EvaluatedArg::ULong(ulong)),
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::ULong, token.span()))
+            },
+            Self::Double => match value.parse::<f64>() {
+                Ok(double) => Ok(EvaluatedArg::Double(double)),
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::Double, token.span()))
+            },
+            Self::Char => match value.parse::<char>() {
+                Ok(c) => Ok(EvaluatedArg::Char(c)),
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::Char, token.span()))
+            },
+            Self::Path => Ok(EvaluatedArg::Path(PathBuf::from(value))),
+            Self::Enum(choices) => match choices.contains(&value.as_str()) {
+                true => Ok(EvaluatedArg::Enum(value)),
+                false => Err(CommandError::mismatch(&value, self.clone(), token.span()))
+            },
+            Self::Duration => match parse_duration(&value) {
+                Some(duration) => Ok(EvaluatedArg::Duration(duration)),
+                None => Err(CommandError::mismatch(&value, ArgType::Duration, token.span()))
+            },
+            Self::Bytes => match parse_bytes(&value) {
+                Some(bytes) => Ok(EvaluatedArg::Bytes(bytes)),
+                None => Err(CommandError::mismatch(&value, ArgType::Bytes, token.span()))
+            },
+            Self::IpAddr => match value.parse::<IpAddr>() {
+                Ok(ip) => Ok(EvaluatedArg::IpAddr(ip)),
+                Err(_) => Err(CommandError::mismatch(&value, ArgType::IpAddr, token.span()))
+            },
+            Self::DateTime => match parse_date_time(&value) {
+                Some(date_time) => Ok(EvaluatedArg::DateTime(date_time)),
+                None => Err(CommandError::mismatch(&value, ArgType::DateTime, token.span()))
+            },
+            Self::Custom(custom) => match (custom.parse)(token) {
+                Some(custom_value) => Ok(EvaluatedArg::Custom(custom_value)),
+                None => Err(CommandError::mismatch(&value, self.clone(), token.span()))
+            },
+            Self::List(inner, separator) => {
+                let elements: Vec<&str> = if value.is_empty() { vec![] } else { value.split(*separator).collect() };
+                let mut list = vec![];
+
+                for element in elements {
+                    list.push(inner.evaluate(&ArgToken::Literal(element.to_string(), token.span()))?);
+                }
+
+                Ok(EvaluatedArg::List(list))
+            },
+            Self::Tuple(inners, separator) => {
+                let elements: Vec<&str> = value.split(*separator).collect();
+                let mut tuple = vec![];
+
+                if elements.len() != inners.len() {
+                    return Err(CommandError::mismatch(&value, self.clone(), token.span()));
+                }
+
+                for (inner, element) in inners.iter().zip(elements) {
+                    tuple.push(inner.evaluate(&ArgToken::Literal(element.to_string(), token.span()))?);
+                }
+
+                Ok(EvaluatedArg::Tuple(tuple))
+            }
         }
     }
 }
@@ -100,4 +435,159 @@ impl EvaluatedArg {
             _ => None
         }
     }
+
+    pub fn get_long(&self) -> Option<i64> {
+        match self {
+            Self::Long(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_ulong(&self) -> Option<u64> {
+        match self {
+            Self::ULong(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_double(&self) -> Option<f64> {
+        match self {
+            Self::Double(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_char(&self) -> Option<char> {
+        match self {
+            Self::Char(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Path(result) => Some(result.clone()),
+            _ => None
+        }
+    }
+
+    pub fn get_enum(&self) -> Option<String> {
+        match self {
+            Self::Enum(result) => Some(result.clone()),
+            _ => None
+        }
+    }
+
+    pub fn get_duration(&self) -> Option<Duration> {
+        match self {
+            Self::Duration(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_bytes(&self) -> Option<u64> {
+        match self {
+            Self::Bytes(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_ip_addr(&self) -> Option<IpAddr> {
+        match self {
+            Self::IpAddr(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_date_time(&self) -> Option<NaiveDateTime> {
+        match self {
+            Self::DateTime(result) => Some(*result),
+            _ => None
+        }
+    }
+
+    pub fn get_list(&self) -> Option<Vec<EvaluatedArg>> {
+        match self {
+            Self::List(result) => Some(result.clone()),
+            _ => None
+        }
+    }
+
+    pub fn get_tuple(&self) -> Option<Vec<EvaluatedArg>> {
+        match self {
+            Self::Tuple(result) => Some(result.clone()),
+            _ => None
+        }
+    }
+
+    pub fn get_custom<T: CustomArgType>(&self) -> Option<T> {
+        match self {
+            Self::Custom(result) => result.downcast_ref::<T>().cloned(),
+            _ => None
+        }
+    }
+}
+
+// Parses durations like 500ms, 5s, 2m, 1h or 1d, units can be combined as in 1h30m
+fn parse_duration(text: &str) -> Option<Duration> {
+    static UNIT: OnceLock<Regex> = OnceLock::new();
+    let unit = UNIT.get_or_init(|| Regex::new(r"^(\d+(?:\.\d+)?)(ms|s|m|h|d)").unwrap());
+    let mut rest = text;
+    let mut seconds = 0.0;
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let captures = unit.captures(rest)?;
+        let amount: f64 = captures[1].parse().ok()?;
+
+        seconds += amount * match &captures[2] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => 86400.0
+        };
+
+        rest = &rest[captures[0].len()..];
+    }
+
+    Duration::try_from_secs_f64(seconds).ok() // Too long durations are a mismatch instead of a panic
+}
+
+// Parses byte sizes like 512, 10KB, 1.5MB or 2GiB, KB is 1000 bytes and KiB is 1024 bytes
+fn parse_bytes(text: &str) -> Option<u64> {
+    static SIZE: OnceLock<Regex> = OnceLock::new();
+    let captures = SIZE.get_or_init(|| Regex::new(r"^(\d+(?:\.\d+)?)\s*([a-zA-Z]*)$").unwrap()).captures(text)?;
+    let amount: f64 = captures[1].parse().ok()?;
+
+    let multiplier: u64 = match captures[2].to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1_000,
+        "M" | "MB" => 1_000_000,
+        "G" | "GB" => 1_000_000_000,
+        "T" | "TB" => 1_000_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        "TIB" => 1 << 40,
+        _ => return None
+    };
+
+    let bytes = (amount * multiplier as f64).round();
+
+    if bytes < u64::MAX as f64 { Some(bytes as u64) } else { None } // The cast would saturate instead of failing
+}
+
+// Parses dates as YYYY-MM-DD with an optional time as HH:MM or HH:MM:SS, separated by a space or a T
+fn parse_date_time(text: &str) -> Option<NaiveDateTime> {
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(date_time);
+        }
+    }
+
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)
 }
\ No newline at end of file
@@ -1,24 +1,47 @@
-use crate::{error::CommandError, inout::read::ArgToken, shell::Shell};
+use std::{fmt::Debug, sync::Arc};
 
-use super::{argument::{ArgType, EvaluatedArg}, instance::CommandInstance};
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+
+use crate::{error::CommandError, inout::{log::LogLevel, read::ArgToken}, shell::Shell};
+
+use super::{argument::{ArgDefinition, ArgList, ArgType, EvaluatedArg}, instance::CommandInstance, suggest, typed::FromArgs, validator::Validator};
+
+pub type Callback = Arc<dyn Fn(&Shell, &ArgList) + Send + Sync>;
+
+#[cfg(feature = "async")]
+pub type AsyncCallback = Arc<dyn for<'a> Fn(&'a Shell, &'a ArgList) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> + Send + Sync>;
 
-#[derive(Debug)]
 pub struct CommandDefinition {
     name: &'static str,
-    arg_list: Vec<ArgType>,
-    callback: fn(&Shell, &Vec<EvaluatedArg>),
-    description: &'static str
+    arg_list: Vec<ArgDefinition>,
+    callback: Callback,
+    #[cfg(feature = "async")]
+    async_callback: Option<AsyncCallback>,
+    description: &'static str,
+    category: &'static str
 }
 
 impl Clone for CommandDefinition {
     fn clone(&self) -> Self {
-        Self { name: self.name.clone(), arg_list: self.arg_list.clone(), callback: self.callback.clone() , description: self.description.clone()}
+        Self { name: self.name, arg_list: self.arg_list.clone(), callback: self.callback.clone(), #[cfg(feature = "async")] async_callback: self.async_callback.clone(), description: self.description, category: self.category }
+    }
+}
+
+impl Debug for CommandDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandDefinition")
+            .field("name", &self.name)
+            .field("arg_list", &self.arg_list)
+            .field("description", &self.description)
+            .field("category", &self.category)
+            .finish()
     }
 }
 
 impl<'a> CommandDefinition {
     pub fn new(name: &'static str) -> Self {
-        Self { name: name, arg_list: vec![], callback: |_shell, _args| {  }, description: "" }
+        Self { name, arg_list: vec![], callback: Arc::new(|_shell, _args| {  }), #[cfg(feature = "async")] async_callback: None, description: "", category: "" }
     }
 
     pub fn build(&self) -> CommandDefinition {
@@ -26,7 +49,34 @@ impl<'a> CommandDefinition {
     }
 
     pub fn add_arg(&mut self, arg_type: ArgType) -> &mut Self {
-        self.arg_list.push(arg_type);
+        self.push_arg(ArgDefinition::new(arg_type))
+    }
+
+    pub fn add_named_arg(&mut self, name: &'static str, arg_type: ArgType, description: &'static str) -> &mut Self {
+        self.push_arg(ArgDefinition::new(arg_type).set_name(name).set_description(description).build())
+    }
+
+    // Optional arguments can be omitted by the user, so they must come after all the required ones
+    pub fn add_optional_arg(&mut self, name: &'static str, arg_type: ArgType, description: &'static str) -> &mut Self {
+        self.push_arg(ArgDefinition::new(arg_type).set_name(name).set_description(description).set_optional(true).build())
+    }
+
+    fn push_arg(&mut self, arg: ArgDefinition) -> &mut Self {
+        if !arg.is_optional() && self.arg_list.last().is_some_and(|last| last.is_optional()) {
+            panic!("Can't add a required argument after an optional one in {}", self.name);
+        }
+
+        self.arg_list.push(arg);
+
+        self
+    }
+
+    // Adds a validator to the last added argument
+    pub fn add_validator(&mut self, validator: Validator) -> &mut Self {
+        match self.arg_list.last_mut() {
+            Some(arg) => { arg.add_validator(validator); },
+            None => panic!("Can't add a validator to {} before adding an argument", self.name)
+        }
 
         self
     }
@@ -37,45 +87,178 @@ impl<'a> CommandDefinition {
         self
     }
 
-    pub fn set_callback(&mut self, callback: fn(&Shell, &Vec<EvaluatedArg>)) -> &mut Self {
-        self.callback = callback;
+    // The section of the help page the command is listed in, the commands without one are listed under Other
+    pub fn set_category(&mut self, category: &'static str) -> &mut Self {
+        self.category = category;
+
+        self
+    }
+
+    pub fn set_callback(&mut self, callback: impl Fn(&Shell, &ArgList) + Send + Sync + 'static) -> &mut Self {
+        self.callback = Arc::new(callback);
+        #[cfg(feature = "async")]
+        { self.async_callback = None; }
+
+        self
+    }
+
+    // Replaces the callback with one that returns a future, like |shell, args| Box::pin(async move { ... })
+    #[cfg(feature = "async")]
+    pub fn set_async_callback(&mut self, callback: impl for<'b> Fn(&'b Shell, &'b ArgList) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>> + Send + Sync + 'static) -> &mut Self {
+        self.async_callback = Some(Arc::new(callback));
+
+        self
+    }
+
+    // Replaces the argument list with the one described by T and calls the callback with the converted struct
+    pub fn set_typed_callback<T: FromArgs>(&mut self, callback: impl Fn(&Shell, T) + Send + Sync + 'static) -> &mut Self {
+        let names = T::arg_names();
+
+        self.arg_list = T::arg_types().into_iter().enumerate()
+            .map(|(i, arg_type)| ArgDefinition::new(arg_type).set_name(names.get(i).copied().unwrap_or("")).build())
+            .collect();
+        let name = self.name;
+        self.callback = Arc::new(move |shell, args| {
+            match T::from_args(args) {
+                Some(typed_args) => callback(shell, typed_args),
+                None => { // instantiate checked the types, so only a from_args that disagrees with its own arg_types gets here
+                    shell.log(LogLevel::ERROR, &format!("The arguments of {} can't be converted into its typed arguments", name));
+                    shell.set_status(1);
+                }
+            }
+        });
+        #[cfg(feature = "async")]
+        { self.async_callback = None; }
 
         self
     }
     
 
 
-    pub fn instantiate(&'a self, shell: &'a Shell, arg_list: Vec<ArgToken>) -> Result<CommandInstance, CommandError>{
-        if arg_list.len() > self.arg_list.len() { 
-            return Err(CommandError::TooManyArguments(self.name.to_string(), self.arg_list.len(), arg_list.len())) 
+    // Positional tokens fill the arguments in order, --name value sets a named argument anywhere in the line and --name alone turns a Bool one on
+    pub fn instantiate(&'a self, shell: &'a Shell, arg_list: Vec<ArgToken>) -> Result<CommandInstance<'a>, CommandError>{
+        let arg_list = self.assign_tokens(arg_list)?;
+
+        let mut inst_arg_list: Vec<EvaluatedArg> = vec![];
+
+        for (i, (arg, token)) in self.arg_list.iter().zip(arg_list.iter()).enumerate() {
+            match arg.arg_type().evaluate(token) {
+                Ok(eval) => {
+                    if let Some(validator) = arg.validators().iter().find(|validator| !validator.validate(&token.to_string(), &eval)) {
+                        return Err(CommandError::InvalidArgument { command: self.name.to_string(), argument: arg.label(i), constraint: validator.to_string(), span: token.span() });
+                    }
+
+                    inst_arg_list.push(eval)
+                },
+                Err(CommandError::MismatchArgument { value, expected, suggestions, span, .. }) => return Err(CommandError::MismatchArgument { value, expected, argument: Some(arg.label(i)), suggestions, span }),
+                Err(e) => return Err(e)
+            }
         }
 
-        else if arg_list.len() < self.arg_list.len() { 
-            return Err(CommandError::TooFewArguments(self.name.to_string(), self.arg_list.len(), arg_list.len())) 
+
+        let names = self.arg_list.iter().map(|arg| arg.name()).collect();
+
+        let instance = CommandInstance::new(shell, self.name, ArgList::new(inst_arg_list, names), self.callback.clone());
+
+        #[cfg(feature = "async")]
+        let instance = instance.set_async_callback(self.async_callback.clone());
+
+        Ok(instance)
+    }
+
+    // Puts the tokens in the order of the argument list, the optional arguments that weren't given are left out of the end
+    fn assign_tokens(&self, tokens: Vec<ArgToken>) -> Result<Vec<ArgToken>, CommandError> {
+        let required = self.arg_list.iter().filter(|arg| !arg.is_optional()).count();
+        let given = tokens.iter().filter(|token| matches!(token, ArgToken::Literal(_, _)) || self.is_switch(token)).count();
+        let end = tokens.last().map(|token| token.span().after()).unwrap_or_default();
+
+        let mut slots: Vec<Option<ArgToken>> = self.arg_list.iter().map(|_| None).collect();
+        let mut positional = vec![];
+        let mut tokens = tokens.into_iter();
+
+        while let Some(token) = tokens.next() {
+            let (name, span) = match token {
+                ArgToken::Flag(name, span) => (name, span),
+                literal => { positional.push(literal); continue; }
+            };
+
+            let index = match self.arg_list.iter().position(|arg| !arg.name().is_empty() && arg.name() == name) {
+                Some(index) => index,
+                None => {
+                    let suggestions = suggest::suggestions(&name, self.arg_list.iter().map(|arg| arg.name()).filter(|name| !name.is_empty()));
+
+                    return Err(CommandError::UnknownArgument { command: self.name.to_string(), name, suggestions, span });
+                }
+            };
+
+            if matches!(self.arg_list[index].arg_type(), ArgType::Bool) { // A switch, the next token isn't its value
+                slots[index] = Some(ArgToken::Literal("true".to_string(), span));
+                continue;
+            }
+
+            slots[index] = match tokens.next() {
+                Some(value @ ArgToken::Literal(_, _)) => Some(value),
+                _ => return Err(CommandError::TooFewArguments { command: self.name.to_string(), expected: required, got: given, missing: self.arg_list[index].label(index), span: span.after() })
+            };
         }
 
-        let mut inst_arg_list: Vec<EvaluatedArg> = vec![];
-        let mut arg_list = arg_list.iter();
+        let mut positional = positional.into_iter();
 
-        for arg in &self.arg_list {
-            match arg.evaluate(arg_list.next().unwrap()) {
-                Ok(eval) => inst_arg_list.push(eval),
-                Err(e) => return Err(e)
+        for slot in slots.iter_mut().filter(|slot| slot.is_none()) {
+            match positional.next() {
+                Some(token) => *slot = Some(token),
+                None => break
             }
         }
 
+        if let Some(extra) = positional.next() {
+            return Err(CommandError::TooManyArguments { command: self.name.to_string(), expected: self.arg_list.len(), got: given, span: extra.span() });
+        }
 
-        Ok(CommandInstance::new(shell, inst_arg_list, self.callback))
+        // The values can't have gaps, so an argument given after a missing one counts as missing it, even if that one is optional
+        let filled = slots.iter().take_while(|slot| slot.is_some()).count();
+
+        if (filled..slots.len()).any(|i| !self.arg_list[i].is_optional() || slots[i].is_some()) {
+            return Err(CommandError::TooFewArguments { command: self.name.to_string(), expected: required, got: given, missing: self.arg_list[filled].label(filled), span: end });
+        }
+
+        Ok(slots.into_iter().flatten().collect())
+    }
+
+    fn is_switch(&self, token: &ArgToken) -> bool {
+        matches!(token, ArgToken::Flag(name, _) if self.arg_list.iter().any(|arg| arg.name() == name && matches!(arg.arg_type(), ArgType::Bool)))
     }
 
     pub fn name(&self) -> &str {
-        &self.name
+        self.name
     }
-    pub fn arg_list(&self) -> &Vec<ArgType> {
+    // The definitions of the arguments, with their names, descriptions and validators
+    pub fn arg_list(&self) -> &Vec<ArgDefinition> {
         &self.arg_list
     }
 
+    // Just the types of the arguments, as arg_list returned before arguments had names
+    pub fn arg_types(&self) -> Vec<ArgType> {
+        self.arg_list.iter().map(|arg| arg.arg_type().clone()).collect()
+    }
+
     pub fn description(&self) -> &'static str {
         self.description
     }
+
+    pub fn category(&self) -> &'static str {
+        self.category
+    }
+
+    // The usage line generated from the argument list, like history <len:Int>
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+
+        for arg in &self.arg_list {
+            usage.push(' ');
+            usage.push_str(&arg.usage());
+        }
+
+        usage
+    }
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, env, fs, io::{self, Read}, path::{Path, PathBuf}, process::{Child, Command, ExitStatus, Stdio}, thread, time::Duration};
+
+use crate::inout::{input::Input, interrupt::CancellationToken, output::Output};
+
+
+// Looks the program up on the PATH, a name with a slash in it is taken as a path instead
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    if name.is_empty() {
+        return None;
+    }
+
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+
+        return if is_executable(&path) { Some(path) } else { None };
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+// Runs the program with the shell variables exported to it and returns its exit status
+// The terminal and the redirected files are handed to the child, a buffer gets what it prints, and cancelling the token kills it
+pub fn run_program(path: &Path, args: &[String], variables: &HashMap<String, String>, input: &Input, output: &Output, token: &CancellationToken) -> io::Result<i32> {
+    let mut command = Command::new(path);
+    command.args(args).envs(variables);
+
+    match output {
+        Output::Stdout => {},
+        Output::Buffer(_) => { command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()); },
+        Output::File(file) => { command.stdout(file.lock().unwrap().try_clone()?); }
+    }
+
+    if let Input::File(reader) = input {
+        command.stdin(reader.lock().unwrap().get_ref().try_clone()?);
+    }
+
+    let mut child = command.spawn()?;
+    let readers = [child.stdout.take().map(drain), child.stderr.take().map(drain)];
+    let status = wait(&mut child, token)?;
+
+    for reader in readers.into_iter().flatten() {
+        if let Ok(bytes) = reader.join() {
+            output.write_str(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    Ok(exit_code(status))
+}
+
+fn wait(child: &mut Child, token: &CancellationToken) -> io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if token.is_cancelled() {
+            let _ = child.kill(); // It may have exited in the meantime
+            return child.wait();
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Reads the pipe on a thread of its own, so a child that prints a lot doesn't block on a full pipe
+fn drain<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = vec![];
+        let _ = pipe.read_to_end(&mut bytes);
+
+        bytes
+    })
+}
+
+// A program killed by a signal gets 128 plus the signal number, as in other shells
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+
+    status.code().unwrap_or(1)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).map(|meta| meta.is_file()).unwrap_or(false)
+}
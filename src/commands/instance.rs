@@ -1,19 +1,65 @@
-use crate::shell::Shell;
+use crate::{error::CommandError, shell::Shell};
 
-use super::argument::EvaluatedArg;
+use super::{argument::ArgList, definition::Callback};
+
+#[cfg(feature = "async")]
+use super::definition::AsyncCallback;
 
 pub struct CommandInstance<'a> {
     shell: &'a Shell,
-    arg_list: Vec<EvaluatedArg>,
-    callback: fn(&Shell, &Vec<EvaluatedArg>)
+    command: String,
+    arg_list: ArgList,
+    callback: Callback,
+    #[cfg(feature = "async")]
+    async_callback: Option<AsyncCallback>
 }
 
 impl<'a> CommandInstance<'a> {
-    pub fn new(shell: &'a Shell, arg_list: Vec<EvaluatedArg>, callback: fn(&Shell, &Vec<EvaluatedArg>)) -> Self {
-        CommandInstance { shell, arg_list, callback }
+    pub fn new(shell: &'a Shell, command: &str, arg_list: ArgList, callback: Callback) -> Self {
+        CommandInstance { shell, command: command.to_string(), arg_list, callback, #[cfg(feature = "async")] async_callback: None }
+    }
+
+    #[cfg(feature = "async")]
+    pub fn set_async_callback(mut self, async_callback: Option<AsyncCallback>) -> Self {
+        self.async_callback = async_callback;
+
+        self
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn arg_list(&self) -> &ArgList {
+        &self.arg_list
+    }
+
+    // Async callbacks are run to completion on a runtime of their own when the shell isn't run with run_async
+    // That can't be done from inside a runtime, where they must be awaited with run_async instead
+    pub fn run(&self) -> Result<(), CommandError> {
+        #[cfg(feature = "async")]
+        if let Some(async_callback) = &self.async_callback {
+            if tokio::runtime::Handle::try_current().is_ok() {
+                return Err(CommandError::InsideRuntime { command: self.command.clone() });
+            }
+
+            tokio::runtime::Builder::new_current_thread().enable_all().build()
+                .expect("Couldn't start a runtime for the async callback")
+                .block_on(async_callback(self.shell, &self.arg_list));
+
+            return Ok(());
+        }
+
+        (self.callback)(self.shell, &self.arg_list);
+
+        Ok(())
     }
 
-    pub fn run(&self) {
-        (self.callback)(self.shell, &self.arg_list)
+    #[cfg(feature = "async")]
+    pub async fn run_async(&self) {
+        match &self.async_callback {
+            Some(async_callback) => async_callback(self.shell, &self.arg_list).await,
+            None => (self.callback)(self.shell, &self.arg_list)
+        }
     }
 }
\ No newline at end of file
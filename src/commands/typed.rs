@@ -0,0 +1,72 @@
+use std::{net::IpAddr, path::PathBuf, time::Duration};
+
+use chrono::NaiveDateTime;
+
+use super::argument::{ArgType, CustomArgType, EvaluatedArg};
+
+// A Rust type that can be read from a single command argument
+pub trait FromArg: Sized {
+    fn arg_type() -> ArgType;
+
+    fn from_arg(arg: &EvaluatedArg) -> Option<Self>;
+}
+
+// A struct that can be built from the whole argument list of a command, usually derived with #[derive(FromArgs)]
+pub trait FromArgs: Sized {
+    fn arg_types() -> Vec<ArgType>;
+
+    // The names shown in help pages, the derive macro uses the field names
+    fn arg_names() -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn from_args(args: &[EvaluatedArg]) -> Option<Self>;
+}
+
+macro_rules! impl_from_arg {
+    ($rust_type:ty, $arg_type:ident, $getter:ident) => {
+        impl FromArg for $rust_type {
+            fn arg_type() -> ArgType {
+                ArgType::$arg_type
+            }
+
+            fn from_arg(arg: &EvaluatedArg) -> Option<Self> {
+                arg.$getter()
+            }
+        }
+    };
+}
+
+impl_from_arg!(String, Str, get_str);
+impl_from_arg!(i32, Int, get_int);
+impl_from_arg!(f32, Float, get_float);
+impl_from_arg!(bool, Bool, get_bool);
+impl_from_arg!(i64, Long, get_long);
+impl_from_arg!(u64, ULong, get_ulong);
+impl_from_arg!(f64, Double, get_double);
+impl_from_arg!(char, Char, get_char);
+impl_from_arg!(PathBuf, Path, get_path);
+impl_from_arg!(Duration, Duration, get_duration);
+impl_from_arg!(IpAddr, IpAddr, get_ip_addr);
+impl_from_arg!(NaiveDateTime, DateTime, get_date_time);
+
+// Vectors are read as comma separated lists
+impl<T: FromArg> FromArg for Vec<T> {
+    fn arg_type() -> ArgType {
+        ArgType::list(T::arg_type(), ',')
+    }
+
+    fn from_arg(arg: &EvaluatedArg) -> Option<Self> {
+        arg.get_list()?.iter().map(T::from_arg).collect()
+    }
+}
+
+impl<T: CustomArgType> FromArg for T {
+    fn arg_type() -> ArgType {
+        ArgType::custom::<T>()
+    }
+
+    fn from_arg(arg: &EvaluatedArg) -> Option<Self> {
+        arg.get_custom::<T>()
+    }
+}
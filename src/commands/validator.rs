@@ -0,0 +1,147 @@
+use std::{cmp::Ordering, fmt::{Debug, Display}, ops::{Bound, RangeBounds}};
+
+use regex::Regex;
+
+use super::argument::EvaluatedArg;
+
+#[derive(Clone)]
+pub enum Validator {
+    Range(Bound<Number>, Bound<Number>),
+    Regex(Regex),
+    Predicate(fn(&EvaluatedArg) -> bool, &'static str)
+}
+
+// A bound or a value of a range validator, integers are kept exact so the whole i64 and u64 ranges can be checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Integer(i128),
+    Float(f64)
+}
+
+// The types a range validator can be built from
+pub trait RangeNumber: Copy {
+    fn to_number(self) -> Number;
+}
+
+macro_rules! impl_range_number {
+    ($variant:ident as $target:ty: $($rust_type:ty),*) => {
+        $(
+            impl RangeNumber for $rust_type {
+                fn to_number(self) -> Number {
+                    Number::$variant(self as $target)
+                }
+            }
+        )*
+    };
+}
+
+impl_range_number!(Integer as i128: i8, i16, i32, i64, u8, u16, u32, u64, usize);
+impl_range_number!(Float as f64: f32, f64);
+
+impl Validator {
+    // Accepts numeric arguments inside the range, as in Validator::range(1..=65535) or Validator::range(0..u64::MAX)
+    pub fn range<T: RangeNumber>(range: impl RangeBounds<T>) -> Self {
+        let convert = |bound: Bound<&T>| match bound {
+            Bound::Included(value) => Bound::Included(value.to_number()),
+            Bound::Excluded(value) => Bound::Excluded(value.to_number()),
+            Bound::Unbounded => Bound::Unbounded
+        };
+
+        Self::Range(convert(range.start_bound()), convert(range.end_bound()))
+    }
+
+    // Accepts arguments whose typed text fully matches the pattern, panics if the pattern is invalid
+    pub fn regex(pattern: &str) -> Self {
+        match Regex::new(&format!("^(?:{})$", pattern)) {
+            Ok(regex) => Self::Regex(regex),
+            Err(e) => panic!("Invalid validator pattern {}: {}", pattern, e)
+        }
+    }
+
+    // Accepts arguments for which the predicate returns true, the description tells the user what's expected
+    pub fn predicate(predicate: fn(&EvaluatedArg) -> bool, description: &'static str) -> Self {
+        Self::Predicate(predicate, description)
+    }
+
+    pub fn validate(&self, text: &str, value: &EvaluatedArg) -> bool {
+        match self {
+            Self::Range(start, end) => match as_number(value) {
+                Some(number) => (*start, *end).contains(&number),
+                None => false
+            },
+            Self::Regex(regex) => regex.is_match(text),
+            Self::Predicate(predicate, _) => predicate(value)
+        }
+    }
+}
+
+fn as_number(value: &EvaluatedArg) -> Option<Number> {
+    match value {
+        EvaluatedArg::Int(number) => Some(number.to_number()),
+        EvaluatedArg::Float(number) => Some(number.to_number()),
+        EvaluatedArg::Long(number) => Some(number.to_number()),
+        EvaluatedArg::ULong(number) => Some(number.to_number()),
+        EvaluatedArg::Double(number) => Some(number.to_number()),
+        EvaluatedArg::Bytes(number) => Some(number.to_number()),
+        EvaluatedArg::Duration(duration) => Some(duration.as_secs_f64().to_number()),
+        _ => None
+    }
+}
+
+// Integers are compared exactly, anything with a float is compared as floats
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a.partial_cmp(b),
+            _ => self.as_f64().partial_cmp(&other.as_f64())
+        }
+    }
+}
+
+impl Number {
+    fn as_f64(&self) -> f64 {
+        match self {
+            Number::Integer(number) => *number as f64,
+            Number::Float(number) => *number
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Integer(number) => write!(f, "{}", number),
+            Number::Float(number) => write!(f, "{}", number)
+        }
+    }
+}
+
+impl Display for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Range(start, end) => match (start, end) {
+                (Bound::Included(start), Bound::Included(end)) => write!(f, "between {} and {}", start, end),
+                (Bound::Included(start), Bound::Excluded(end)) => write!(f, "at least {} and less than {}", start, end),
+                (Bound::Excluded(start), Bound::Included(end)) => write!(f, "greater than {} and at most {}", start, end),
+                (Bound::Excluded(start), Bound::Excluded(end)) => write!(f, "greater than {} and less than {}", start, end),
+                (Bound::Included(start), Bound::Unbounded) => write!(f, "at least {}", start),
+                (Bound::Excluded(start), Bound::Unbounded) => write!(f, "greater than {}", start),
+                (Bound::Unbounded, Bound::Included(end)) => write!(f, "at most {}", end),
+                (Bound::Unbounded, Bound::Excluded(end)) => write!(f, "less than {}", end),
+                (Bound::Unbounded, Bound::Unbounded) => write!(f, "a number")
+            },
+            Self::Regex(regex) => {
+                let pattern = regex.as_str();
+
+                write!(f, "matching {}", pattern.strip_prefix("^(?:").and_then(|inner| inner.strip_suffix(")$")).unwrap_or(pattern))
+            },
+            Self::Predicate(_, description) => write!(f, "{}", description)
+        }
+    }
+}
+
+impl Debug for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Validator({})", self)
+    }
+}
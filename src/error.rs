@@ -1,24 +1,29 @@
 use std::{fmt::Display, io, error::Error};
 
-use crate::commands::argument::ArgType;
+use crate::{commands::argument::ArgType, inout::read::Span};
 
 
 #[derive(Debug)]
 pub enum InputError{
     EmptyInput,
-    NotACommand(String),
+    NotACommand(String, Span),
     NotAEnvVarAttrib(String),
-    InterfaceError(io::Error)
+    InterfaceError(io::Error),
+    MissingRedirection(String, Span),
+    RedirectionFile(String, io::Error, Span)
 }
 
 #[derive(Debug)]
 pub enum CommandError {
-    MismatchArgument(String, ArgType),
-    NoCallback(String),
-    TooManyArguments(String, usize, usize),
-    TooFewArguments(String, usize, usize),
-    UnknownArgument(String),
-    UnknownCommand(String)
+    MismatchArgument { value: String, expected: Box<ArgType>, argument: Option<String>, suggestions: Vec<String>, span: Span },
+    NoCallback { command: String },
+    TooManyArguments { command: String, expected: usize, got: usize, span: Span },
+    TooFewArguments { command: String, expected: usize, got: usize, missing: String, span: Span },
+    UnknownArgument { command: String, name: String, suggestions: Vec<String>, span: Span },
+    UnknownCommand { name: String, suggestions: Vec<String>, span: Span },
+    InvalidArgument { command: String, argument: String, constraint: String, span: Span },
+    ProgramNotFound { name: String, span: Span },
+    InsideRuntime { command: String }
 }
 
 
@@ -28,31 +33,119 @@ pub enum EnvVarError {
     Mismatch(String, String)
 }
 
+// A configuration method couldn't apply the given value
+#[derive(Debug)]
+pub enum ConfigError {
+    LogDirectory(String, io::Error),
+    LogPattern(String)
+}
+
+// Any error the shell can run into while being configured or while reading and running a line
+#[derive(Debug)]
+pub enum ShellError {
+    Input(InputError),
+    Command(CommandError),
+    EnvVar(EnvVarError),
+    Config(ConfigError)
+}
 
-impl Error for InputError {}
+
+impl Error for InputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InterfaceError(error) | Self::RedirectionFile(_, error, _) => Some(error),
+            _ => None
+        }
+    }
+}
 
 impl Display for InputError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::EmptyInput => write!(f, "Empty input"),
-            Self::NotACommand(cmd) => write!(f, "{} isn't a command", cmd),
+            Self::NotACommand(cmd, _) => write!(f, "{} isn't a command", cmd),
             Self::NotAEnvVarAttrib(env_var) => write!(f, "{} isn't a proper environment variable attribution", env_var),
-            Self::InterfaceError(error) => write!(f, "Command-line input error {}", error)
+            Self::InterfaceError(error) => write!(f, "Command-line input error {}", error),
+            Self::MissingRedirection(operator, _) => write!(f, "Missing the file name after {}", operator),
+            Self::RedirectionFile(path, error, _) => write!(f, "Couldn't open {} for the redirection: {}", path, error)
+        }
+    }
+}
+
+impl InputError {
+    // See ShellError::code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::EmptyInput => "E100",
+            Self::NotACommand(_, _) => "E101",
+            Self::NotAEnvVarAttrib(_) => "E102",
+            Self::InterfaceError(_) => "E103",
+            Self::MissingRedirection(_, _) => "E104",
+            Self::RedirectionFile(_, _, _) => "E105"
+        }
+    }
+
+    // See ShellError::span
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::NotACommand(_, span) => Some(*span),
+            Self::MissingRedirection(_, span) => Some(*span),
+            Self::RedirectionFile(_, _, span) => Some(*span),
+            _ => None
         }
     }
 }
 
 impl Error for CommandError {}
 
+impl CommandError {
+    // See ShellError::code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MismatchArgument { .. } => "E200",
+            Self::NoCallback { .. } => "E201",
+            Self::TooManyArguments { .. } => "E202",
+            Self::TooFewArguments { .. } => "E203",
+            Self::UnknownArgument { .. } => "E204",
+            Self::UnknownCommand { .. } => "E205",
+            Self::InvalidArgument { .. } => "E206",
+            Self::ProgramNotFound { .. } => "E207",
+            Self::InsideRuntime { .. } => "E208"
+        }
+    }
+
+    // See ShellError::span
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::MismatchArgument { span, .. }
+            | Self::TooManyArguments { span, .. }
+            | Self::TooFewArguments { span, .. }
+            | Self::UnknownArgument { span, .. }
+            | Self::UnknownCommand { span, .. }
+            | Self::InvalidArgument { span, .. }
+            | Self::ProgramNotFound { span, .. } => Some(*span),
+            Self::NoCallback { .. } | Self::InsideRuntime { .. } => None
+        }
+    }
+
+    // The value of the argument didn't fit its type, the argument name and the suggestions are filled in later
+    pub fn mismatch(value: &str, expected: ArgType, span: Span) -> Self {
+        Self::MismatchArgument { value: value.to_string(), expected: Box::new(expected), argument: None, suggestions: vec![], span }
+    }
+}
+
 impl Clone for CommandError {
     fn clone(&self) -> Self {
         match self {
-            Self::MismatchArgument(arg0, arg1) => Self::MismatchArgument(arg0.clone(), arg1.clone()),
-            Self::NoCallback(arg0) => Self::NoCallback(arg0.clone()),
-            Self::TooManyArguments(arg0, arg1, arg2) => Self::TooManyArguments(arg0.clone(), arg1.clone(), arg2.clone()),
-            Self::TooFewArguments(arg0, arg1, arg2) => Self::TooFewArguments(arg0.clone(), arg1.clone(), arg2.clone()),
-            Self::UnknownArgument(arg0) => Self::UnknownArgument(arg0.clone()),
-            Self::UnknownCommand(arg0) => Self::UnknownCommand(arg0.clone()),
+            Self::MismatchArgument { value, expected, argument, suggestions, span } => Self::MismatchArgument { value: value.clone(), expected: expected.clone(), argument: argument.clone(), suggestions: suggestions.clone(), span: *span },
+            Self::NoCallback { command } => Self::NoCallback { command: command.clone() },
+            Self::TooManyArguments { command, expected, got, span } => Self::TooManyArguments { command: command.clone(), expected: *expected, got: *got, span: *span },
+            Self::TooFewArguments { command, expected, got, missing, span } => Self::TooFewArguments { command: command.clone(), expected: *expected, got: *got, missing: missing.clone(), span: *span },
+            Self::UnknownArgument { command, name, suggestions, span } => Self::UnknownArgument { command: command.clone(), name: name.clone(), suggestions: suggestions.clone(), span: *span },
+            Self::UnknownCommand { name, suggestions, span } => Self::UnknownCommand { name: name.clone(), suggestions: suggestions.clone(), span: *span },
+            Self::InvalidArgument { command, argument, constraint, span } => Self::InvalidArgument { command: command.clone(), argument: argument.clone(), constraint: constraint.clone(), span: *span },
+            Self::ProgramNotFound { name, span } => Self::ProgramNotFound { name: name.clone(), span: *span },
+            Self::InsideRuntime { command } => Self::InsideRuntime { command: command.clone() },
         }
     }
 }
@@ -60,12 +153,37 @@ impl Clone for CommandError {
 impl Display for CommandError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::MismatchArgument(arg, arg_type) => write!(f, "Mismatch argument type, can convert {} into {}", arg, arg_type),
-            Self::NoCallback(name) => write!(f, "No callback defined for {}", name),
-            Self::TooManyArguments(name, expected, got)  => write!(f, "Too many arguments for {}, expected {}, but got {}", name, expected, got),
-            Self::TooFewArguments(name, expected, got)  => write!(f, "Too few arguments for {}, expected {}, but got {}", name, expected, got),
-            Self::UnknownArgument(arg) => write!(f, "Unknown argument {}", arg),
-            Self::UnknownCommand(name) => write!(f, "Unknown command {}", name),
+            Self::MismatchArgument { value, expected, argument: None, suggestions, .. } => write!(f, "Mismatch argument type, can convert {} into {}{}", value, expected, did_you_mean(suggestions)),
+            Self::MismatchArgument { value, expected, argument: Some(argument), suggestions, .. } => write!(f, "Mismatch argument type for {}, can convert {} into {}{}", argument, value, expected, did_you_mean(suggestions)),
+            Self::NoCallback { command } => write!(f, "No callback defined for {}", command),
+            Self::TooManyArguments { command, expected, got, .. } => write!(f, "Too many arguments for {}, expected {}, but got {}", command, expected, got),
+            Self::TooFewArguments { command, expected, got, missing, .. } => write!(f, "Too few arguments for {}, expected {}, but got {}, missing {}", command, expected, got, missing),
+            Self::UnknownArgument { command, name, suggestions, .. } => write!(f, "Unknown argument --{} for {}{}", name, command, did_you_mean(&suggestions.iter().map(|suggestion| format!("--{}", suggestion)).collect::<Vec<String>>())),
+            Self::UnknownCommand { name, suggestions, .. } => write!(f, "Unknown command {}{}", name, did_you_mean(suggestions)),
+            Self::InvalidArgument { command, argument, constraint, .. } => write!(f, "Invalid argument {} for {}, it must be {}", argument, command, constraint),
+            Self::ProgramNotFound { name, .. } => write!(f, "Couldn't find the program {} on the PATH", name),
+            Self::InsideRuntime { command } => write!(f, "Can't run the async callback of {} inside a runtime, use run_async or source_async", command),
+        }
+    }
+}
+
+// Formats the suggestions as ", did you mean a, b or c?", or nothing if there are none
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [single] => format!(", did you mean {}?", single),
+        [rest @ .., last] => format!(", did you mean {} or {}?", rest.join(", "), last)
+    }
+}
+
+impl Error for EnvVarError {}
+
+impl EnvVarError {
+    // See ShellError::code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unset(_) => "E300",
+            Self::Mismatch(_, _) => "E301"
         }
     }
 }
@@ -86,4 +204,100 @@ impl Display for EnvVarError {
             Self::Mismatch(name, value) => write!(f, "{} environment variable with value {} can't be casted to desired type", name, value)
         }
     }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::LogDirectory(_, error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl ConfigError {
+    // See ShellError::code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::LogDirectory(_, _) => "E400",
+            Self::LogPattern(_) => "E401"
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LogDirectory(directory, error) => write!(f, "Couldn't use {} as the log directory: {}", directory, error),
+            Self::LogPattern(pattern) => write!(f, "{} isn't a valid log file pattern", pattern)
+        }
+    }
+}
+
+// A transparent wrapper, Display already shows the wrapped error, so its source is the one of the wrapped error
+impl Error for ShellError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Input(error) => error.source(),
+            Self::Command(error) => error.source(),
+            Self::EnvVar(error) => error.source(),
+            Self::Config(error) => error.source()
+        }
+    }
+}
+
+impl ShellError {
+    // A stable identifier like E201 for the kind of error, it doesn't change when the message wording does
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Input(error) => error.code(),
+            Self::Command(error) => error.code(),
+            Self::EnvVar(error) => error.code(),
+            Self::Config(error) => error.code()
+        }
+    }
+
+    // Where in the line the error is, if it's about a single token
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Input(error) => error.span(),
+            Self::Command(error) => error.span(),
+            Self::EnvVar(_) | Self::Config(_) => None
+        }
+    }
+}
+
+impl From<InputError> for ShellError {
+    fn from(error: InputError) -> Self {
+        Self::Input(error)
+    }
+}
+
+impl From<CommandError> for ShellError {
+    fn from(error: CommandError) -> Self {
+        Self::Command(error)
+    }
+}
+
+impl From<EnvVarError> for ShellError {
+    fn from(error: EnvVarError) -> Self {
+        Self::EnvVar(error)
+    }
+}
+
+impl From<ConfigError> for ShellError {
+    fn from(error: ConfigError) -> Self {
+        Self::Config(error)
+    }
+}
+
+impl Display for ShellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input(error) => write!(f, "{}", error),
+            Self::Command(error) => write!(f, "{}", error),
+            Self::EnvVar(error) => write!(f, "{}", error),
+            Self::Config(error) => write!(f, "{}", error)
+        }
+    }
 }
\ No newline at end of file
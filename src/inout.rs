@@ -1,2 +1,10 @@
 pub mod read;
-pub mod log;
\ No newline at end of file
+pub mod log;
+pub mod prompt;
+pub mod term;
+pub mod style;
+pub mod interrupt;
+pub mod input;
+pub mod output;
+#[cfg(feature = "log")]
+pub mod facade;
\ No newline at end of file
@@ -0,0 +1,41 @@
+use std::sync::{Arc, Mutex};
+
+use super::log::{LogLevel, Logger};
+
+// Forwards the records of the log crate to the logger of a shell, so they follow the same thresholds and go to the same file
+pub struct ShellLogger(Arc<Mutex<Logger>>);
+
+impl ShellLogger {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        Self(logger)
+    }
+}
+
+impl ::log::Log for ShellLogger {
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {
+        let logger = self.0.lock().unwrap();
+        let level = LogLevel::from(metadata.level());
+
+        level >= logger.terminal_level() || level >= logger.file_level()
+    }
+
+    fn log(&self, record: &::log::Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().write(record.level().into(), &format!("{}: {}", record.target(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl From<::log::Level> for LogLevel {
+    fn from(level: ::log::Level) -> Self {
+        match level {
+            ::log::Level::Error => LogLevel::ERROR,
+            ::log::Level::Warn => LogLevel::WARN,
+            ::log::Level::Info => LogLevel::INFO,
+            ::log::Level::Debug => LogLevel::DEBUG,
+            ::log::Level::Trace => LogLevel::TRACE
+        }
+    }
+}
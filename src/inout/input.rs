@@ -0,0 +1,46 @@
+use std::{fs::File, io::{self, BufRead, BufReader, Read}, sync::{Arc, Mutex}};
+
+// Where the commands read their input from, the terminal or a file given with <
+#[derive(Debug, Clone, Default)]
+pub enum Input {
+    #[default]
+    Stdin,
+    File(Arc<Mutex<BufReader<File>>>)
+}
+
+impl Input {
+    pub fn file(file: File) -> Self {
+        Input::File(Arc::new(Mutex::new(BufReader::new(file))))
+    }
+
+    // Reads a line along with its line break, None once the input ends
+    pub fn read_line(&self) -> Option<String> {
+        let mut line = String::new();
+
+        let read = match self {
+            Input::Stdin => io::stdin().read_line(&mut line),
+            Input::File(reader) => reader.lock().unwrap().read_line(&mut line)
+        };
+
+        match read {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line)
+        }
+    }
+
+    // Reads everything that is left
+    pub fn read_to_string(&self) -> io::Result<String> {
+        let mut text = String::new();
+
+        match self {
+            Input::Stdin => io::stdin().read_to_string(&mut text)?,
+            Input::File(reader) => reader.lock().unwrap().read_to_string(&mut text)?
+        };
+
+        Ok(text)
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Input::Stdin)
+    }
+}
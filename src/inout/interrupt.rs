@@ -0,0 +1,197 @@
+use std::{fmt::Debug, io::{self, Write}, process, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex, OnceLock}};
+
+// What a second Ctrl-C does while the command is still running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterruptPolicy {
+    Exit,
+    Ignore
+}
+
+// Set when the user presses Ctrl-C while a command runs, long callbacks should poll it and stop early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    #[cfg(feature = "async")]
+    notify: tokio::sync::Notify
+}
+
+// The interrupt state of a shell, it can be cloned and used from other threads to interrupt it
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(Arc<Interrupts>);
+
+// Shuts the shell down with the given code, right before a second Ctrl-C ends the process
+pub(crate) type ExitFn = Arc<dyn Fn(i32) + Send + Sync>;
+
+struct Interrupts {
+    token: Mutex<CancellationToken>,
+    running: AtomicBool,
+    count: AtomicUsize,
+    prompt: Mutex<String>,
+    policy: Mutex<InterruptPolicy>,
+    on_exit: Mutex<Option<ExitFn>>
+}
+
+// The shell the SIGINT handler sends the interrupts to, the last one that started running lines
+static ACTIVE: Mutex<Option<InterruptHandle>> = Mutex::new(None);
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+
+        #[cfg(feature = "async")]
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Completes once the token is cancelled
+    #[cfg(feature = "async")]
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.0.notify.notified(); // Registered before checking, so a cancel in between isn't missed
+
+            if self.is_cancelled() {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
+impl InterruptHandle {
+    pub fn new() -> Self {
+        Self(Arc::new(Interrupts {
+            token: Mutex::new(CancellationToken::new()),
+            running: AtomicBool::new(false),
+            count: AtomicUsize::new(0),
+            prompt: Mutex::new(String::new()),
+            policy: Mutex::new(InterruptPolicy::Exit),
+            on_exit: Mutex::new(None)
+        }))
+    }
+
+    pub fn set_policy(&self, policy: InterruptPolicy) {
+        *self.0.policy.lock().unwrap() = policy;
+    }
+
+    pub fn policy(&self) -> InterruptPolicy {
+        *self.0.policy.lock().unwrap()
+    }
+
+    // The token of the command that is running, or of the next one
+    pub fn token(&self) -> CancellationToken {
+        self.0.token.lock().unwrap().clone()
+    }
+
+    // Does what Ctrl-C does: cancels the running command, or clears the line at the prompt
+    pub fn interrupt(&self) {
+        if !self.0.running.load(Ordering::SeqCst) {
+            print!("\n{}", self.0.prompt.lock().unwrap()); // The terminal already dropped what was typed
+            let _ = io::stdout().flush();
+
+            return;
+        }
+
+        self.token().cancel();
+
+        if self.0.count.fetch_add(1, Ordering::SeqCst) >= 1 && *self.0.policy.lock().unwrap() == InterruptPolicy::Exit {
+            eprintln!();
+
+            let on_exit = self.0.on_exit.lock().unwrap().clone();
+            if let Some(on_exit) = on_exit {
+                on_exit(130);
+            }
+
+            process::exit(130); // The conventional status of a process ended by SIGINT
+        }
+    }
+
+    // Gives the next line a fresh token, so it isn't taken as cancelled because of the previous command
+    pub(crate) fn start_line(&self) {
+        *self.0.token.lock().unwrap() = CancellationToken::new();
+    }
+
+    // Sends the interrupts to this shell while the command runs, on_exit runs if the user forces the exit
+    pub(crate) fn start_command(&self, on_exit: ExitFn) {
+        *self.0.on_exit.lock().unwrap() = Some(on_exit);
+        self.0.count.store(0, Ordering::SeqCst);
+        self.0.running.store(true, Ordering::SeqCst);
+
+        self.activate();
+    }
+
+    pub(crate) fn finish_command(&self) {
+        self.0.running.store(false, Ordering::SeqCst);
+        *self.0.on_exit.lock().unwrap() = None;
+    }
+
+    // Remembers the prompt so it can be drawn again when Ctrl-C clears the line
+    pub(crate) fn set_prompt(&self, prompt: &str) {
+        *self.0.prompt.lock().unwrap() = prompt.to_string();
+    }
+
+    pub(crate) fn activate(&self) {
+        let mut active = ACTIVE.lock().unwrap();
+
+        if !self.is_active(&active) {
+            *active = Some(self.clone());
+        }
+    }
+
+    // Stops sending the interrupts to this shell, Ctrl-C ends the process again unless another shell is active
+    pub(crate) fn deactivate(&self) {
+        let mut active = ACTIVE.lock().unwrap();
+
+        if self.is_active(&active) {
+            *active = None;
+        }
+    }
+
+    fn is_active(&self, active: &Option<InterruptHandle>) -> bool {
+        active.as_ref().is_some_and(|handle| Arc::ptr_eq(&handle.0, &self.0))
+    }
+}
+
+impl Debug for Interrupts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interrupts")
+            .field("token", &self.token)
+            .field("running", &self.running)
+            .field("count", &self.count)
+            .field("prompt", &self.prompt)
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for InterruptHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Replaces the default SIGINT behaviour, that ends the process, with interrupting the active shell
+// The handler can't be removed, so without an active shell it ends the process as the default one would
+pub(crate) fn install() {
+    INSTALLED.get_or_init(|| {
+        ctrlc::set_handler(|| {
+            let active = ACTIVE.lock().unwrap().clone();
+
+            match active {
+                Some(handle) => handle.interrupt(),
+                None => process::exit(130)
+            }
+        }).is_ok()
+    });
+}
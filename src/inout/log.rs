@@ -1,31 +1,485 @@
-use std::{fmt::Display, fs::OpenOptions, io::Write, path::Path};
+use std::{cell::RefCell, fmt::Display, fs::{self, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, str::FromStr, time::{Duration, SystemTime}};
 
+use chrono::{Local, NaiveDate, format::{self, Item, Parsed, StrftimeItems}};
+
+use super::{output::Output, style::{ColorMode, Style, Stream, Theme}};
+
+thread_local! {
+    // Where the terminal entries written from this thread go instead, the buffer of the background job running on it
+    static TERMINAL: RefCell<Option<Output>> = const { RefCell::new(None) };
+}
+
+// Ordered from the most verbose to the most severe, so levels can be compared against a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
+    TRACE,
+    DEBUG,
     INFO,
     WARN,
     ERROR
 }
 
-pub fn log(file: &str, log_level: LogLevel, message: &str) {
-    let to_log = format!("{} {}\n", log_level, message);
-    
-    match log_level {
-        LogLevel::ERROR => eprintln!("{}", &to_log[..to_log.len()-1]),
-        LogLevel::WARN => println!("{}", &to_log[..to_log.len()-1]),
-        _ => {} 
+// How the entries are written to the log file, the terminal always gets text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    JsonLines
+}
+
+// The details of a command run, attached to its log entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandRecord {
+    pub name: String,
+    pub args: Vec<String>,
+    pub status: i32,
+    pub duration: Duration
+}
+
+// Where the file entries go once the log file can't be written anymore
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogFallback {
+    Disable,
+    Stderr,
+    Directory(PathBuf)
+}
+
+// When the shell stops writing to the current log file and starts a new one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Never,
+    Size(u64),
+    Daily
+}
+
+// Which of the old log files are kept when a new one is started
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retention {
+    Forever,
+    Count(usize),
+    Age(Duration)
+}
+
+// The log file of a shell, named after a chrono format pattern inside a directory
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    directory: Option<PathBuf>,
+    pattern: String,
+    rotation: Rotation,
+    retention: Retention,
+    format: LogFormat,
+    current: Option<(PathBuf, NaiveDate)>
+}
+
+// Writes the entries of a shell to the terminal and the log file, shared with the other producers like the log crate
+#[derive(Debug)]
+pub struct Logger {
+    file: LogFile,
+    session_id: String,
+    terminal_level: LogLevel,
+    file_level: LogLevel,
+    terminal_override: Option<LogLevel>,
+    file_override: Option<LogLevel>,
+    theme: Theme,
+    color_mode: ColorMode,
+    fallback: LogFallback,
+    to_stderr: bool
+}
+
+impl Logger {
+    pub fn new(session_id: &str) -> Self {
+        Self { file: LogFile::new(), session_id: session_id.to_string(), terminal_level: LogLevel::WARN, file_level: LogLevel::INFO, terminal_override: None, file_override: None, theme: Theme::default(), color_mode: ColorMode::Auto, fallback: LogFallback::Disable, to_stderr: false }
+    }
+
+    pub fn file(&mut self) -> &mut LogFile {
+        &mut self.file
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.file.path()
+    }
+
+    pub fn set_terminal_level(&mut self, log_level: LogLevel) {
+        self.terminal_level = log_level;
+    }
+
+    pub fn set_file_level(&mut self, log_level: LogLevel) {
+        self.file_level = log_level;
+    }
+
+    // The levels that replace the configured thresholds while they are set, from $LOG_LEVEL and $LOG_FILE_LEVEL
+    pub fn set_overrides(&mut self, terminal: Option<LogLevel>, file: Option<LogLevel>) {
+        self.terminal_override = terminal;
+        self.file_override = file;
+    }
+
+    pub fn set_fallback(&mut self, fallback: LogFallback) {
+        self.fallback = fallback;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        self.color_mode = color_mode;
+    }
+
+    // The thresholds in use are the configured ones, unless an override is set
+    pub fn terminal_level(&self) -> LogLevel {
+        self.terminal_override.unwrap_or(self.terminal_level)
+    }
+
+    pub fn file_level(&self) -> LogLevel {
+        self.file_override.unwrap_or(self.file_level)
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn color_mode(&self) -> ColorMode {
+        self.color_mode
+    }
+
+    pub fn write(&mut self, log_level: LogLevel, message: &str) {
+        self.write_entry(log_level, message, None);
+    }
+
+    // Writes the entry of a command run, the file gets the details of the record too
+    pub fn write_command(&mut self, log_level: LogLevel, message: &str, command: &CommandRecord) {
+        self.write_entry(log_level, message, Some(command));
+    }
+
+    // Shows the message on the terminal and writes it to the log file, each one only if the level reaches its threshold
+    fn write_entry(&mut self, log_level: LogLevel, message: &str, command: Option<&CommandRecord>) {
+        if log_level >= self.terminal_level() {
+            self.show(log_level, message);
+        }
+
+        if log_level >= self.file_level() && (self.file.is_enabled() || self.to_stderr) {
+            let entry = self.file.entry(&self.session_id, log_level, message, command);
+
+            self.append(&entry);
+        }
+    }
+
+    // Writes the entry to the file, on failure warns once and switches to the fallback
+    fn append(&mut self, entry: &str) {
+        if self.to_stderr {
+            return eprint!("{}", entry);
+        }
+
+        let error = match self.file.append(entry) {
+            Ok(()) => return,
+            Err(error) => error
+        };
+
+        let file = self.file.path().or(self.file.directory()).map(|path| path.display().to_string()).unwrap_or_default();
+        self.file.disable();
+
+        match self.fallback.clone() {
+            LogFallback::Disable => self.warn(&format!("Couldn't write to the log file {}: {}, file logging is disabled", file, error)),
+            LogFallback::Stderr => {
+                self.warn(&format!("Couldn't write to the log file {}: {}, the entries will go to stderr", file, error));
+                self.to_stderr = true;
+                eprint!("{}", entry);
+            },
+            LogFallback::Directory(directory) => {
+                self.warn(&format!("Couldn't write to the log file {}: {}, switching to {}", file, error, directory.display()));
+                self.fallback = LogFallback::Disable; // If the fallback fails too, the logging stops
+
+                match fs::create_dir_all(&directory) {
+                    Ok(()) => { self.file.set_directory(&directory); self.append(entry); },
+                    Err(error) => self.warn(&format!("Couldn't create the log directory {}: {}, file logging is disabled", directory.display(), error))
+                }
+            }
+        }
+    }
+
+    // Shown regardless of the thresholds, as the user would otherwise miss that entries are being lost
+    fn warn(&self, message: &str) {
+        self.show(LogLevel::WARN, message);
+    }
+
+    // Prints the message on the terminal, or adds it to the job buffer when it's written from a background job
+    fn show(&self, log_level: LogLevel, message: &str) {
+        if let Some(output) = TERMINAL.with(|terminal| terminal.borrow().clone()) {
+            return output.write_str(&format!("{} {}\n", log_level, message));
+        }
+
+        let style = if self.color_mode.enabled(log_level.stream()) { self.theme.log_style(&log_level) } else { Style::new() };
+
+        print(log_level, message, &style);
+    }
+}
+
+// Sends the terminal entries written from the current thread to the output, or back to the terminal with None
+pub(crate) fn redirect_terminal(output: Option<Output>) {
+    TERMINAL.with(|terminal| *terminal.borrow_mut() = output);
+}
+
+// Shows the message on the terminal using the given style
+pub fn print(log_level: LogLevel, message: &str, style: &Style) {
+    let to_print = format!("{} {}", log_level, message);
+
+    match log_level.stream() {
+        Stream::Stderr => eprintln!("{}", style.paint(&to_print)),
+        Stream::Stdout => println!("{}", style.paint(&to_print))
     };
+}
+
+impl LogFile {
+    pub fn new() -> Self {
+        Self { directory: None, pattern: "diysh-%Y-%m-%d_%H-%M-%S.log".to_string(), rotation: Rotation::Never, retention: Retention::Forever, format: LogFormat::Text, current: None }
+    }
+
+    pub fn set_directory(&mut self, directory: &Path) -> &mut Self {
+        self.directory = Some(directory.to_path_buf());
+        self.current = None;
+
+        self
+    }
+
+    // The file name is the current time formatted with this pattern, like diysh-%Y-%m-%d.log
+    pub fn set_pattern(&mut self, pattern: &str) -> &mut Self {
+        self.pattern = pattern.to_string();
+        self.current = None;
+
+        self
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) -> &mut Self {
+        self.rotation = rotation;
+
+        self
+    }
+
+    pub fn set_retention(&mut self, retention: Retention) -> &mut Self {
+        self.retention = retention;
+
+        self
+    }
+
+    pub fn set_format(&mut self, format: LogFormat) -> &mut Self {
+        self.format = format;
+
+        self
+    }
+
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    // The file being written to, None until the first entry
+    pub fn path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    // The line written for the message, with a timestamp and the id of the session that wrote it
+    pub fn entry(&self, session_id: &str, log_level: LogLevel, message: &str, command: Option<&CommandRecord>) -> String {
+        let timestamp = Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string();
+
+        match (self.format, command) {
+            (LogFormat::Text, None) => format!("{} {} {} {}\n", timestamp, session_id, log_level, message),
+            (LogFormat::Text, Some(command)) => format!("{} {} {} {} [status {}, {:?}]\n", timestamp, session_id, log_level, message, command.status, command.duration),
+            (LogFormat::JsonLines, _) => format!("{}\n", json_entry(&timestamp, session_id, log_level, message, command))
+        }
+    }
+
+    // Appends the entry to the file, starting a new file first if it's time to rotate
+    pub fn append(&mut self, entry: &str) -> io::Result<()> {
+        if self.directory.is_none() {
+            return Ok(());
+        }
+
+        if self.current.is_none() || self.should_rotate() {
+            self.rotate()?;
+        }
+
+        OpenOptions::new().create(true).append(true).open(self.path().unwrap())?
+            .write_all(entry.as_bytes())
+    }
+
+    // Stops writing to any file until a directory is set again
+    pub fn disable(&mut self) {
+        self.directory = None;
+        self.current = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.directory.is_some()
+    }
+
+    fn should_rotate(&self) -> bool {
+        match (&self.current, self.rotation) {
+            (Some((path, _)), Rotation::Size(limit)) => fs::metadata(path).is_ok_and(|metadata| metadata.len() >= limit),
+            (Some((_, opened)), Rotation::Daily) => *opened != Local::now().date_naive(),
+            _ => false
+        }
+    }
+
+    // Picks the file for the current time, numbering it as name.1, name.2... if that one is already full
+    fn rotate(&mut self) -> io::Result<()> {
+        let directory = self.directory.clone().unwrap();
+        let name = safe_file_name(&Local::now().format(&self.pattern).to_string());
+        let mut path = directory.join(&name);
+
+        if let Rotation::Size(limit) = self.rotation {
+            let mut n = 0;
+
+            while fs::metadata(&path).is_ok_and(|metadata| metadata.len() >= limit) {
+                n += 1;
+                path = directory.join(format!("{}.{}", name, n));
+            }
+        }
+
+        OpenOptions::new().create(true).append(true).open(&path)?;
+        self.current = Some((path, Local::now().date_naive()));
+
+        self.clean_up();
+
+        Ok(())
+    }
+
+    // Deletes the old files of this pattern that are beyond the retention limit, the current one is always kept
+    fn clean_up(&self) {
+        let (directory, current) = match (&self.directory, self.path()) {
+            (Some(directory), Some(current)) => (directory, current),
+            _ => return
+        };
+
+        let mut files: Vec<(PathBuf, SystemTime)> = match fs::read_dir(directory) {
+            Ok(entries) => entries.flatten()
+                .filter(|entry| entry.path() != current && matches_pattern(&self.pattern, &entry.file_name().to_string_lossy()))
+                .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.modified().ok()?)))
+                .collect(),
+            Err(_) => return
+        };
+
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0))); // Newest first, files written in the same instant go by name
 
-    OpenOptions::new().append(true).open(Path::new(file)).unwrap()
-        .write(&to_log.as_bytes())
-        .expect("Couldn't write to the log file!");
+        let expired: Vec<&PathBuf> = match self.retention {
+            Retention::Forever => vec![],
+            Retention::Count(count) => files.iter().skip(count.saturating_sub(1)).map(|(path, _)| path).collect(),
+            Retention::Age(age) => files.iter()
+                .filter(|(_, modified)| modified.elapsed().is_ok_and(|elapsed| elapsed > age))
+                .map(|(path, _)| path)
+                .collect()
+        };
+
+        for path in expired {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Default for LogFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Keeps the formatted name inside the log directory and free of characters some file systems reject
+fn safe_file_name(name: &str) -> String {
+    name.chars().map(|c| match c {
+        '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+        _ => c
+    }).collect()
+}
+
+// Whether the pattern produces the name, or its numbered version from size rotation
+// The name must parse back with the exact pattern, so the other files of a shared directory are never taken for old logs
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let items: Vec<Item> = StrftimeItems::new(pattern).map(|item| match item { // The names went through safe_file_name
+        Item::Literal(text) => Item::OwnedLiteral(safe_file_name(text).into()),
+        Item::OwnedLiteral(text) => Item::OwnedLiteral(safe_file_name(&text).into()),
+        item => item
+    }).collect();
+
+    let parses = |name: &str| format::parse(&mut Parsed::new(), name, items.iter()).is_ok();
+
+    parses(name) || name.rsplit_once('.').is_some_and(|(base, n)| !n.is_empty() && n.bytes().all(|byte| byte.is_ascii_digit()) && parses(base))
+}
+
+// One JSON object per line, with the command fields only on command entries
+fn json_entry(timestamp: &str, session_id: &str, log_level: LogLevel, message: &str, command: Option<&CommandRecord>) -> String {
+    let mut entry = format!("{{\"timestamp\":{},\"level\":{},\"session_id\":{},\"message\":{}", json_string(timestamp), json_string(log_level.name()), json_string(session_id), json_string(message));
+
+    if let Some(command) = command {
+        let args: Vec<String> = command.args.iter().map(|arg| json_string(arg)).collect();
+
+        entry.push_str(&format!(",\"command\":{},\"args\":[{}],\"status\":{},\"duration_ms\":{}", json_string(&command.name), args.join(","), command.status, command.duration.as_secs_f64() * 1000.0));
+    }
+
+    entry.push('}');
+
+    entry
+}
+
+// Quotes the text as a JSON string
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c)
+        }
+    }
+
+    quoted.push('"');
+
+    quoted
+}
+
+impl LogLevel {
+    // The name of the level without the brackets, like INFO
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::TRACE => "TRACE",
+            LogLevel::DEBUG => "DEBUG",
+            LogLevel::INFO => "INFO",
+            LogLevel::WARN => "WARN",
+            LogLevel::ERROR => "ERROR"
+        }
+    }
+
+    // The terminal stream where messages of this level are shown
+    pub fn stream(&self) -> Stream {
+        match self {
+            LogLevel::ERROR => Stream::Stderr,
+            _ => Stream::Stdout
+        }
+    }
 }
 
 impl Display for LogLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            LogLevel::INFO => write!(f, "[INFO]"),
-            LogLevel::WARN => write!(f, "[WARN]"),
-            LogLevel::ERROR => write!(f, "[ERROR]")
+        write!(f, "[{}]", self.name())
+    }
+}
+
+// Parses the level names in any case, like the values of $LOG_LEVEL
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "trace" => Ok(LogLevel::TRACE),
+            "debug" => Ok(LogLevel::DEBUG),
+            "info" => Ok(LogLevel::INFO),
+            "warn" | "warning" => Ok(LogLevel::WARN),
+            "error" => Ok(LogLevel::ERROR),
+            _ => Err(format!("{} isn't a log level, expected trace, debug, info, warn or error", s.trim()))
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,43 @@
+use std::{fs::File, io::{self, Write}, sync::{Arc, Mutex}};
+
+// Where the commands write their output, the terminal, a buffer that can be read later, like the one of a background job, or a file given with > or >>
+#[derive(Debug, Clone, Default)]
+pub enum Output {
+    #[default]
+    Stdout,
+    Buffer(Arc<Mutex<Vec<u8>>>),
+    File(Arc<Mutex<File>>)
+}
+
+impl Output {
+    pub fn buffer() -> Self {
+        Output::Buffer(Arc::new(Mutex::new(vec![])))
+    }
+
+    pub fn file(file: File) -> Self {
+        Output::File(Arc::new(Mutex::new(file)))
+    }
+
+    pub fn write_str(&self, text: &str) {
+        match self {
+            Output::Stdout => {
+                print!("{}", text);
+                let _ = io::stdout().flush();
+            },
+            Output::Buffer(buffer) => buffer.lock().unwrap().extend_from_slice(text.as_bytes()),
+            Output::File(file) => { let _ = file.lock().unwrap().write_all(text.as_bytes()); }
+        }
+    }
+
+    // What was written so far, None for the terminal and the files
+    pub fn contents(&self) -> Option<String> {
+        match self {
+            Output::Stdout | Output::File(_) => None,
+            Output::Buffer(buffer) => Some(String::from_utf8_lossy(&buffer.lock().unwrap()).to_string())
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Output::Stdout)
+    }
+}
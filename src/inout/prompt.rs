@@ -0,0 +1,60 @@
+use std::{fmt::Debug, sync::Arc};
+
+use chrono::Local;
+
+use crate::shell::Shell;
+
+use super::style::Style;
+
+// Computes a piece of the prompt, it may capture the state of the app, like the current connection
+pub type PromptFn = Arc<dyn Fn(&Shell) -> String + Send + Sync>;
+
+#[derive(Clone)]
+pub enum PromptSegment {
+    Text(String),
+    Env(&'static str),
+    Time(&'static str),
+    Status,
+    HistoryNumber,
+    Custom(PromptFn),
+    Styled(Style, Box<PromptSegment>)
+}
+
+impl Debug for PromptSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(text) => f.debug_tuple("Text").field(text).finish(),
+            Self::Env(name) => f.debug_tuple("Env").field(name).finish(),
+            Self::Time(format) => f.debug_tuple("Time").field(format).finish(),
+            Self::Status => write!(f, "Status"),
+            Self::HistoryNumber => write!(f, "HistoryNumber"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+            Self::Styled(style, segment) => f.debug_tuple("Styled").field(style).field(segment).finish()
+        }
+    }
+}
+
+impl PromptSegment {
+    pub fn custom(callback: impl Fn(&Shell) -> String + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(callback))
+    }
+
+    pub fn render(&self, shell: &Shell) -> String {
+        match self {
+            Self::Text(text) => shell.replace_masks(text),
+            Self::Env(name) => shell.get_env_var::<String>(name).unwrap_or_default(),
+            Self::Time(format) => Local::now().format(format).to_string(),
+            Self::Status => shell.last_status().to_string(),
+            Self::HistoryNumber => shell.history_len().to_string(),
+            Self::Custom(callback) => callback(shell),
+            Self::Styled(style, segment) => shell.paint(style, &segment.render(shell))
+        }
+    }
+}
+
+// Evaluates every segment in order and joins the results
+pub fn render(segments: &[PromptSegment], shell: &Shell) -> String {
+    segments.iter()
+        .map(|segment| segment.render(shell))
+        .collect()
+}
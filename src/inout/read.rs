@@ -1,87 +1,213 @@
-use std::{io, collections::HashMap};
+use std::{io, collections::HashMap, fmt::Display};
 use regex::Regex;
 
 use crate::error::InputError;
 
 
+// Where a token is in the line, as a byte range and as a range of columns (counted in chars)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub start_column: usize,
+    pub end_column: usize
+}
+
 #[derive(Debug)]
 pub enum ArgToken {
-    Literal(String),
-    Flag(String)
+    Literal(String, Span),
+    Flag(String, Span)
 }
 
 #[derive(Debug)]
-pub struct IdentifierToken(pub String);
+pub struct IdentifierToken(pub String, pub Span);
+
+// A redirection in the line, with the file and where the file name is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redirection {
+    Write(String, Span), // > file
+    Append(String, Span), // >> file
+    Read(String, Span) // < file
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, start_column: usize, end_column: usize) -> Self {
+        Self { start, end, start_column, end_column }
+    }
+
+    // A one column span right after this one, used to point at something missing
+    pub fn after(&self) -> Self {
+        Self::new(self.end, self.end + 1, self.end_column, self.end_column + 1)
+    }
+}
+
+impl ArgToken {
+    pub fn span(&self) -> Span {
+        match self {
+            ArgToken::Literal(_, span) => *span,
+            ArgToken::Flag(_, span) => *span
+        }
+    }
+}
 
 // Read a line and return a tuple for building the command from the tokens or and error if the input can't be parsed to a command
 pub fn get_tokens(line: &str) -> Result<(IdentifierToken, Vec<ArgToken>), InputError> {
     let mut raw_tokens = get_raw_tokens(line); // Gets the raw_token vector
 
     // Verifies if it isn't an empty input
-    if raw_tokens.len() == 0 {
+    if raw_tokens.is_empty() {
         return Err(InputError::EmptyInput);
     }
 
     // Verifies if the first element is a valid identifier
-    if !is_identifier(&raw_tokens[0]) {
-        return Err(InputError::NotACommand(raw_tokens[0].clone()));
+    if !is_identifier(&raw_tokens[0].0) {
+        return Err(InputError::NotACommand(raw_tokens[0].0.clone(), raw_tokens[0].1));
     }
 
     // Gets the identifier
-    let identifier = IdentifierToken(raw_tokens[0].clone());
+    let identifier = IdentifierToken(raw_tokens[0].0.clone(), raw_tokens[0].1);
     
     // Let the raw token list with only the arguments
     raw_tokens.remove(0);
 
     let mut args: Vec<ArgToken> = vec![];
 
-    // Files the arg vector with the proper tokens
-    for arg in raw_tokens {
-        if is_flag(&arg) { args.push(ArgToken::Flag(arg[2..].to_string())) }
-        else { args.push(ArgToken::Literal(arg)) }
+    // Files the arg vector with the proper tokens, a quoted token is always a value even if it looks like a flag
+    for (arg, span, quoted) in raw_tokens {
+        if !quoted && is_flag(&arg) { args.push(ArgToken::Flag(arg[2..].to_string(), span)) }
+        else { args.push(ArgToken::Literal(arg, span)) }
     }
 
     Ok((identifier, args))
 }
 
+// Takes the redirections out of the line, the quoted > and < are left as they are
+// They are replaced with blanks, so the spans of the remaining tokens still point at the line the user typed
+pub fn get_redirections(line: &str) -> Result<(String, Vec<Redirection>), InputError> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut remaining: Vec<char> = chars.iter().map(|(_, c)| *c).collect();
+    let mut redirections = vec![];
+    let mut quoting = false;
+    let mut column = 0;
+
+    while column < chars.len() {
+        let c = chars[column].1;
+
+        if c == '"' { quoting = !quoting; }
+
+        if quoting || (c != '>' && c != '<') {
+            column += 1;
+            continue;
+        }
+
+        let operator_start = column;
+        let append = c == '>' && chars.get(column + 1).map(|(_, next)| *next) == Some('>');
+        column += if append { 2 } else { 1 };
+
+        while column < chars.len() && chars[column].1.is_whitespace() {
+            column += 1;
+        }
+
+        // The file name runs until a blank or another operator, the quotes may hold them too
+        let name_start = column;
+        let mut name = String::new();
+        let mut quoted = false;
+
+        while column < chars.len() {
+            match chars[column].1 {
+                '"' => quoted = !quoted,
+                c if !quoted && (c.is_whitespace() || c == '>' || c == '<') => break,
+                c => name.push(c)
+            }
+
+            column += 1;
+        }
+
+        let byte = |column: usize| chars.get(column).map(|(i, _)| *i).unwrap_or(line.len());
+
+        if name.is_empty() {
+            let operator = if append { ">>" } else { &line[byte(operator_start)..byte(operator_start + 1)] };
+
+            return Err(InputError::MissingRedirection(operator.to_string(), Span::new(byte(operator_start), byte(column), operator_start, column)));
+        }
+
+        let span = Span::new(byte(name_start), byte(column), name_start, column);
+
+        redirections.push(match (c, append) {
+            ('<', _) => Redirection::Read(name, span),
+            (_, true) => Redirection::Append(name, span),
+            (_, false) => Redirection::Write(name, span)
+        });
+
+        remaining[operator_start..column].fill(' ');
+    }
+
+    Ok((remaining.into_iter().collect(), redirections))
+}
+
+impl Redirection {
+    pub fn path(&self) -> &str {
+        match self {
+            Redirection::Write(path, _) | Redirection::Append(path, _) | Redirection::Read(path, _) => path
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Redirection::Write(_, span) | Redirection::Append(_, span) | Redirection::Read(_, span) => *span
+        }
+    }
+}
+
+// Splits the line into its words, with the quotes removed and nothing else interpreted, as an external program gets them
+pub fn get_words(line: &str) -> Vec<(String, Span)> {
+    get_raw_tokens(line).into_iter().map(|(word, span, _)| (word, span)).collect()
+}
+
 pub fn get_env_var(line: &str) -> Result<(String, String), InputError> {
     let line = line.trim();
 
     if !is_env_var(line) {
         Err(InputError::NotAEnvVarAttrib(line.to_string()))
     } else {
-        let name = (&line[1..line.find("=").unwrap()]).to_string();
-        let value = (&line[line.find("=").unwrap()+1..]).to_string();
+        let name = line[1..line.find('=').unwrap()].to_string();
+        let value = line[line.find('=').unwrap()+1..].to_string();
 
         Ok((name, value))
     }
 }
 
-pub fn read_line() -> String {
+// Reads until a non-blank line, returns None when the input ends (Ctrl-D or a closed stdin) or can't be read
+pub fn read_line() -> Option<String> {
     let mut buf = String::new();
     
     loop {
         match io::stdin().read_line(&mut buf) {
-            Ok(_) => if buf.trim() != "" { break; } 
-            Err(e) => println!("Error: {e}")
+            Ok(0) => return None,
+            Ok(_) => if buf.trim() != "" { return Some(buf); } 
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => { println!("Error: {e}"); return None; }
         }
     }
-
-    buf
 }
 
-fn get_raw_tokens(raw_string: &str) -> Vec<String> {
-    let mut raw_tokens: Vec<String> = vec![];
+// The tokens of the line, along with whether they had quotes in them
+fn get_raw_tokens(raw_string: &str) -> Vec<(String, Span, bool)> {
+    let mut raw_tokens: Vec<(String, Span, bool)> = vec![];
     let mut raw_token = String::new();
     let mut quoting = false;
+    let mut start: Option<(usize, usize)> = None; // The byte and the column where the current token started
     
 
-    for c in raw_string.chars() {
+    for (column, (i, c)) in raw_string.char_indices().enumerate() {
         match c {
             '"' => {
                 if quoting { 
-                    raw_tokens.push(raw_token); 
+                    let (start_byte, start_column) = start.take().unwrap_or((i, column));
+                    raw_tokens.push((raw_token, Span::new(start_byte, i + 1, start_column, column + 1), true)); 
                     raw_token = String::new(); 
+                } else if start.is_none() {
+                    start = Some((i, column));
                 }
                 quoting = !quoting; 
             },
@@ -89,21 +215,35 @@ fn get_raw_tokens(raw_string: &str) -> Vec<String> {
                 if quoting { 
                     raw_token.push(c);
                 
-                } else if raw_token.len() > 0 {
-                    raw_tokens.push(raw_token); 
-                    raw_token = String::new(); 
-                
-                } else {
+                } else if let Some((start_byte, start_column)) = start.take() {
+                    raw_tokens.push((raw_token, Span::new(start_byte, i, start_column, column), false)); 
                     raw_token = String::new(); 
                 }
             },
-            _ => raw_token.push(c)
+            _ => {
+                if start.is_none() {
+                    start = Some((i, column));
+                }
+                raw_token.push(c)
+            }
         }
     }
 
+    // The line may end without a whitespace, like the ones read from files
+    if let (Some((start_byte, start_column)), false) = (start, quoting) {
+        raw_tokens.push((raw_token, Span::new(start_byte, raw_string.len(), start_column, raw_string.chars().count()), false));
+    }
+
     raw_tokens
 }
 
+// Renders the line with a caret underline below the span, as compilers do
+pub fn caret_diagnostic(line: &str, span: Span) -> String {
+    let width = span.end_column.saturating_sub(span.start_column).max(1);
+
+    format!("{}\n{}{}", line.trim_end(), " ".repeat(span.start_column), "^".repeat(width))
+}
+
 pub fn replace_masks(text: String, map: &HashMap<String, String>) -> String {
     let mut text = text;
 
@@ -115,31 +255,22 @@ pub fn replace_masks(text: String, map: &HashMap<String, String>) -> String {
 }
 
 fn is_identifier(text: &str) -> bool {
-    match Regex::new(r"[a-z][a-zA-Z0-9]*").unwrap().captures(text) {
-        None => false,
-        _ => true
-    }
+    Regex::new(r"^[a-z][a-zA-Z0-9]*$").unwrap().is_match(text)
 }
 
 fn is_env_var(text: &str) -> bool {
-    match Regex::new(r"\$[A-Z_]+\=.+").unwrap().captures(text) {
-        None => false,
-        _ => true
-    }
+    Regex::new(r"\$[A-Z_]+\=.+").unwrap().is_match(text)
 }
 
 fn is_flag(text: &str) -> bool {
-    match Regex::new(r"--[a-z][a-zA-Z0-9]*").unwrap().captures(text) {
-        None => false,
-        _ => true
-    }
+    Regex::new(r"^--[a-z][a-zA-Z0-9]*$").unwrap().is_match(text)
 }
 
-impl ToString for ArgToken {
-    fn to_string(&self) -> String {
+impl Display for ArgToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ArgToken::Literal(text) => text.clone(),
-            ArgToken::Flag(text) => text.clone()
+            ArgToken::Literal(text, _) => write!(f, "{}", text),
+            ArgToken::Flag(text, _) => write!(f, "{}", text)
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std::{io::{self, IsTerminal}, sync::OnceLock};
+
+use regex::Regex;
+
+use super::log::LogLevel;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    Fixed(u8),
+    Rgb(u8, u8, u8)
+}
+
+impl Color {
+    // The SGR parameters of this color, the base is 30 for foreground and 40 for background
+    fn code(&self, base: u8) -> String {
+        match self {
+            Self::Black => format!("{}", base),
+            Self::Red => format!("{}", base + 1),
+            Self::Green => format!("{}", base + 2),
+            Self::Yellow => format!("{}", base + 3),
+            Self::Blue => format!("{}", base + 4),
+            Self::Magenta => format!("{}", base + 5),
+            Self::Cyan => format!("{}", base + 6),
+            Self::White => format!("{}", base + 7),
+            Self::BrightBlack => format!("{}", base + 60),
+            Self::BrightRed => format!("{}", base + 61),
+            Self::BrightGreen => format!("{}", base + 62),
+            Self::BrightYellow => format!("{}", base + 63),
+            Self::BrightBlue => format!("{}", base + 64),
+            Self::BrightMagenta => format!("{}", base + 65),
+            Self::BrightCyan => format!("{}", base + 66),
+            Self::BrightWhite => format!("{}", base + 67),
+            Self::Fixed(n) => format!("{};5;{}", base + 8, n),
+            Self::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Style {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn build(&self) -> Style {
+        *self
+    }
+
+    pub fn set_foreground(&mut self, color: Color) -> &mut Self {
+        self.foreground = Some(color);
+
+        self
+    }
+
+    pub fn set_background(&mut self, color: Color) -> &mut Self {
+        self.background = Some(color);
+
+        self
+    }
+
+    pub fn set_bold(&mut self, bold: bool) -> &mut Self {
+        self.bold = bold;
+
+        self
+    }
+
+    pub fn set_dim(&mut self, dim: bool) -> &mut Self {
+        self.dim = dim;
+
+        self
+    }
+
+    pub fn set_italic(&mut self, italic: bool) -> &mut Self {
+        self.italic = italic;
+
+        self
+    }
+
+    pub fn set_underline(&mut self, underline: bool) -> &mut Self {
+        self.underline = underline;
+
+        self
+    }
+
+    // Wraps the text with the escape sequences of this style, a plain style leaves the text untouched
+    pub fn paint(&self, text: &str) -> String {
+        let mut codes: Vec<String> = vec![];
+
+        if self.bold { codes.push("1".to_string()) }
+        if self.dim { codes.push("2".to_string()) }
+        if self.italic { codes.push("3".to_string()) }
+        if self.underline { codes.push("4".to_string()) }
+        if let Some(color) = self.foreground { codes.push(color.code(30)) }
+        if let Some(color) = self.background { codes.push(color.code(40)) }
+
+        if codes.is_empty() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    trace: Style,
+    debug: Style,
+    info: Style,
+    warn: Style,
+    error: Style,
+    command: Style
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        Self {
+            trace: Style::new(),
+            debug: Style::new(),
+            info: Style::new(),
+            warn: Style::new(),
+            error: Style::new(),
+            command: Style::new()
+        }
+    }
+
+    pub fn build(&self) -> Theme {
+        self.clone()
+    }
+
+    pub fn set_log_style(&mut self, log_level: LogLevel, style: Style) -> &mut Self {
+        match log_level {
+            LogLevel::TRACE => self.trace = style,
+            LogLevel::DEBUG => self.debug = style,
+            LogLevel::INFO => self.info = style,
+            LogLevel::WARN => self.warn = style,
+            LogLevel::ERROR => self.error = style
+        }
+
+        self
+    }
+
+    pub fn set_command_style(&mut self, style: Style) -> &mut Self {
+        self.command = style;
+
+        self
+    }
+
+    pub fn log_style(&self, log_level: &LogLevel) -> Style {
+        match log_level {
+            LogLevel::TRACE => self.trace,
+            LogLevel::DEBUG => self.debug,
+            LogLevel::INFO => self.info,
+            LogLevel::WARN => self.warn,
+            LogLevel::ERROR => self.error
+        }
+    }
+
+    pub fn command_style(&self) -> Style {
+        self.command
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::new()
+            .set_log_style(LogLevel::TRACE, Style::new().set_dim(true).build())
+            .set_log_style(LogLevel::DEBUG, Style::new().set_dim(true).build())
+            .set_log_style(LogLevel::WARN, Style::new().set_foreground(Color::Yellow).build())
+            .set_log_style(LogLevel::ERROR, Style::new().set_foreground(Color::Red).set_bold(true).build())
+            .set_command_style(Style::new().set_foreground(Color::Cyan).set_bold(true).build())
+            .build()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr
+}
+
+impl ColorMode {
+    // In auto mode, colors are used only if $NO_COLOR isn't set and the stream is a terminal
+    pub fn enabled(&self, stream: Stream) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                let no_color = std::env::var("NO_COLOR").map(|value| !value.is_empty()).unwrap_or(false);
+                let is_terminal = match stream {
+                    Stream::Stdout => io::stdout().is_terminal(),
+                    Stream::Stderr => io::stderr().is_terminal()
+                };
+
+                !no_color && is_terminal
+            }
+        }
+    }
+}
+
+// Counts the characters that will actually be displayed, ignoring escape sequences
+pub fn visible_len(text: &str) -> usize {
+    static ESCAPE: OnceLock<Regex> = OnceLock::new();
+    let escape = ESCAPE.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+    escape.replace_all(text, "").chars().count()
+}
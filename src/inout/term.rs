@@ -0,0 +1,53 @@
+use std::env;
+
+const DEFAULT_WIDTH: usize = 80;
+
+// Gets the width of the terminal, falling back to $COLUMNS and then to 80 columns
+pub fn width() -> usize {
+    if let Some(width) = query_width() {
+        return width;
+    }
+
+    match env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()) {
+        Some(width) if width > 0 => width,
+        _ => DEFAULT_WIDTH
+    }
+}
+
+// Breaks the text into lines no wider than width, the lines after the first one start with indent spaces
+pub fn wrap(text: &str, width: usize, indent: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let has_words = !line.trim().is_empty();
+
+        if has_words && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = " ".repeat(indent);
+        } else if has_words {
+            line.push(' ');
+        }
+
+        line.push_str(word);
+    }
+
+    lines.push(line);
+
+    lines
+}
+
+#[cfg(unix)]
+fn query_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 => Some(size.ws_col as usize),
+        _ => None
+    }
+}
+
+#[cfg(not(unix))]
+fn query_width() -> Option<usize> {
+    None
+}
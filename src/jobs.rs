@@ -0,0 +1,123 @@
+use std::{fmt::Display, sync::{Arc, Mutex}, thread::JoinHandle};
+
+use crate::inout::{interrupt::CancellationToken, output::Output};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    Running,
+    Done(i32),
+    Killed
+}
+
+// A command started with & that runs on a thread of its own, writing to its own output buffer
+pub struct Job {
+    id: usize,
+    line: String,
+    token: CancellationToken,
+    output: Output,
+    status: Arc<Mutex<JobStatus>>,
+    handle: Option<JoinHandle<()>>,
+    reported: bool
+}
+
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+    next_id: usize
+}
+
+impl Job {
+    pub fn new(line: &str, token: CancellationToken, output: Output) -> Self {
+        Self { id: 0, line: line.trim_end().to_string(), token, output, status: Arc::new(Mutex::new(JobStatus::Running)), handle: None, reported: false }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    pub fn status(&self) -> JobStatus {
+        *self.status.lock().unwrap()
+    }
+
+    // The handle the job thread sets the status through when it finishes
+    pub fn status_handle(&self) -> Arc<Mutex<JobStatus>> {
+        self.status.clone()
+    }
+
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    pub fn set_handle(&mut self, handle: JoinHandle<()>) {
+        self.handle = Some(handle);
+    }
+
+    // Cancels the job token, the job stops as soon as its callback checks it
+    pub fn kill(&self) {
+        self.token.cancel();
+    }
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds the job with the next id, ids start at 1
+    pub fn add(&mut self, mut job: Job) -> usize {
+        self.next_id += 1;
+        job.id = self.next_id;
+        self.jobs.push(job);
+
+        self.next_id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn jobs(&self) -> &Vec<Job> {
+        &self.jobs
+    }
+
+    // Takes the thread handles of the job, or of every job, so they can be joined without holding the table
+    pub fn take_handles(&mut self, id: Option<usize>) -> Vec<JoinHandle<()>> {
+        self.jobs.iter_mut()
+            .filter(|job| id.is_none_or(|id| job.id == id))
+            .filter_map(|job| job.handle.take())
+            .collect()
+    }
+
+    // The jobs that finished since the last call, so each one is only announced once
+    pub fn take_finished(&mut self) -> Vec<&Job> {
+        let mut finished = vec![];
+
+        for job in self.jobs.iter_mut().filter(|job| !job.reported && job.status() != JobStatus::Running) {
+            job.reported = true;
+            finished.push(job.id);
+        }
+
+        self.jobs.iter().filter(|job| finished.contains(&job.id)).collect()
+    }
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Running => write!(f, "Running"),
+            JobStatus::Done(0) => write!(f, "Done"),
+            JobStatus::Done(status) => write!(f, "Exit {}", status),
+            JobStatus::Killed => write!(f, "Killed")
+        }
+    }
+}
+
+impl Display for Job {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {:<8} {}", self.id, self.status().to_string(), self.line)
+    }
+}
@@ -1,7 +1,14 @@
 pub mod shell;
 pub mod inout;
-mod error;
+pub mod error;
 pub mod commands;
+pub mod jobs;
+
+#[cfg(feature = "derive")]
+pub use diysh_derive::FromArgs;
+
+// Lets the code generated by diysh-derive refer to ::diysh from inside this crate
+extern crate self as diysh;
 
 #[cfg(test)]
 mod tests;
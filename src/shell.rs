@@ -1,8 +1,8 @@
-use std::{collections::HashMap, io::{self, Write}, cmp::min, process, path::Path, fs::{self, OpenOptions}, str::FromStr};
+use std::{collections::HashMap, io::{self, Write, BufRead, BufReader}, cmp::min, process, path::PathBuf, fs::{self, File, OpenOptions}, str::FromStr, env, mem, panic::{self, AssertUnwindSafe}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
 
-use chrono::Local;
+use chrono::{Local, format::{Item, StrftimeItems}};
 
-use crate::{commands::{definition::CommandDefinition, argument::ArgType}, inout::{read, log::{LogLevel, self}}, error::{CommandError, EnvVarError}};
+use crate::{commands::{definition::CommandDefinition, argument::{ArgList, ArgType, EvaluatedArg}, external, instance::CommandInstance, suggest}, inout::{read::{self, Redirection, Span}, interrupt::{self, CancellationToken, ExitFn, InterruptHandle, InterruptPolicy}, input::Input, output::Output, log::{self, CommandRecord, LogFallback, LogFormat, LogLevel, Logger, Rotation, Retention}, prompt::{self, PromptSegment}, term, style::{self, Style, Theme, ColorMode, Stream}}, error::{CommandError, ConfigError, EnvVarError, InputError, ShellError}, jobs::{Job, JobStatus, JobTable}};
 
 
 pub struct Shell {
@@ -10,54 +10,199 @@ pub struct Shell {
     environment_registry: HashMap<String, String>,
     history: Vec<String>,
     do_sparse: bool,
-    log_file: Option<String>
+    logger: Arc<Mutex<Logger>>,
+    rc_file: Option<String>,
+    rc_loaded: bool,
+    prompt: Vec<PromptSegment>,
+    right_prompt: Vec<PromptSegment>,
+    last_status: i32,
+    exit_code: Mutex<Option<i32>>,
+    exit_hooks: Vec<ExitHook>,
+    interrupts: InterruptHandle,
+    handle_interrupts: bool,
+    input: Input,
+    output: Output,
+    jobs: Arc<Mutex<JobTable>>,
+    external_commands: bool,
+    command_status: Mutex<Option<i32>>
 }
 
+pub type ExitHook = Arc<dyn Fn(&Shell, i32) + Send + Sync>;
+
+// How often the wait builtin checks whether the jobs finished
+const JOB_POLL: Duration = Duration::from_millis(10);
+
+// The name and the arguments of the command in a line, once they're parsed
+type LineCommand = (String, Vec<String>);
+
 
 
 impl Shell {
     pub fn new() -> Self {
+        let session_id = format!("{}-{}", Local::now().format("%Y%m%d%H%M%S"), process::id());
+
         Shell {  
             command_registry: HashMap::new(),
             environment_registry: HashMap::new(),
             history: vec![],
             do_sparse: false,
-            log_file: None
+            logger: Arc::new(Mutex::new(Logger::new(&session_id))),
+            rc_file: None,
+            rc_loaded: false,
+            prompt: vec![],
+            right_prompt: vec![],
+            last_status: 0,
+            exit_code: Mutex::new(None),
+            exit_hooks: vec![],
+            interrupts: InterruptHandle::new(),
+            handle_interrupts: true,
+            input: Input::Stdin,
+            output: Output::Stdout,
+            jobs: Arc::new(Mutex::new(JobTable::new())),
+            external_commands: false,
+            command_status: Mutex::new(None)
         }
     }
 
+    // A copy that runs a background job, it shares the logger, and so the log settings, and the job table but writes to its own output
+    fn snapshot(&self, output: Output) -> Shell {
+        let mut snapshot = Shell::new();
+
+        snapshot.command_registry = self.command_registry.clone();
+        snapshot.environment_registry = self.environment_registry.clone();
+        snapshot.rc_loaded = true;
+        snapshot.logger = self.logger.clone();
+        snapshot.handle_interrupts = false;
+        snapshot.interrupts.set_policy(InterruptPolicy::Ignore);
+        snapshot.input = self.input.clone();
+        snapshot.output = output;
+        snapshot.jobs = self.jobs.clone();
+        snapshot.external_commands = self.external_commands;
+
+        snapshot
+    }
+
     pub fn set_sparse(&mut self, do_sparse: bool) -> &mut Self {
         self.do_sparse = do_sparse;
 
         self
     }
 
-    pub fn set_log_directory(&mut self, log_directory: &str) -> &mut Self {
-        let path = Path::new(log_directory);
+    // Runs the programs on the PATH when a line doesn't name a registered command, and lets !name force it
+    pub fn set_external_commands(&mut self, external_commands: bool) -> &mut Self {
+        self.external_commands = external_commands;
 
-        if !path.is_dir() {
-            if let Err(_) = fs::create_dir_all(path) {
-                panic!("Couldn't create the log directory {}", log_directory);
-            }
+        self
+    }
+
+    pub fn set_log_directory(&mut self, log_directory: &str) -> Result<&mut Self, ConfigError> {
+        let path = expand_home(log_directory);
+
+        if let Err(error) = fs::create_dir_all(&path) {
+            return Err(ConfigError::LogDirectory(log_directory.to_string(), error));
         }
 
-        let mut filename = log_directory.to_string();
+        self.logger.lock().unwrap().file().set_directory(&path);
 
-        filename.push_str(&format!("diysh-{}.log", Local::now().format("%Y-%m-%d-%H:%M:%S")));
-    
-        self.log_file = match OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&filename) {
-                Ok(_) => Some(filename.clone()),
-                Err(_) => None,
-            };
+        Ok(self)
+    }
+
+    // The chrono format the log file names follow, diysh-%Y-%m-%d_%H-%M-%S.log by default
+    pub fn set_log_pattern(&mut self, pattern: &str) -> Result<&mut Self, ConfigError> {
+        if pattern.trim().is_empty() || StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+            return Err(ConfigError::LogPattern(pattern.to_string()));
+        }
+
+        self.logger.lock().unwrap().file().set_pattern(pattern);
+
+        Ok(self)
+    }
+
+    // What happens to the file entries if the log file stops being writable, by default they are dropped
+    pub fn set_log_fallback(&mut self, fallback: LogFallback) -> &mut Self {
+        self.logger.lock().unwrap().set_fallback(fallback);
+
+        self
+    }
+
+    pub fn set_log_rotation(&mut self, rotation: Rotation) -> &mut Self {
+        self.logger.lock().unwrap().file().set_rotation(rotation);
+
+        self
+    }
+
+    // Text by default, JsonLines writes one JSON object per entry
+    pub fn set_log_format(&mut self, format: LogFormat) -> &mut Self {
+        self.logger.lock().unwrap().file().set_format(format);
+
+        self
+    }
+
+    pub fn set_log_retention(&mut self, retention: Retention) -> &mut Self {
+        self.logger.lock().unwrap().file().set_retention(retention);
+
+        self
+    }
+
+    // The file the log is being written to, None if there's no log directory or nothing was logged yet
+    pub fn log_path(&self) -> Option<PathBuf> {
+        self.logger.lock().unwrap().path().map(|path| path.to_path_buf())
+    }
+
+    // Only messages at this level or above are shown on the terminal, $LOG_LEVEL overrides it at runtime
+    pub fn set_terminal_log_level(&mut self, log_level: LogLevel) -> &mut Self {
+        self.logger.lock().unwrap().set_terminal_level(log_level);
+
+        self
+    }
+
+    // Only messages at this level or above are written to the log file, $LOG_FILE_LEVEL overrides it at runtime
+    pub fn set_file_log_level(&mut self, log_level: LogLevel) -> &mut Self {
+        self.logger.lock().unwrap().set_file_level(log_level);
+
+        self
+    }
+
+    pub fn set_rc_file(&mut self, rc_file: &str) -> &mut Self {
+        self.rc_file = Some(rc_file.to_string());
+        self.rc_loaded = false;
 
         self
     }
 
     pub fn set_prompt(&mut self, p: &str) -> &mut Self {
         self.set_env("SYSTEM_PROMPT_DEFINITION", p);
+        self.prompt.clear();
+
+        self
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) -> &mut Self {
+        self.logger.lock().unwrap().set_theme(theme);
+
+        self
+    }
+
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) -> &mut Self {
+        self.logger.lock().unwrap().set_color_mode(color_mode);
+
+        self
+    }
+
+    pub fn set_prompt_segments(&mut self, segments: Vec<PromptSegment>) -> &mut Self {
+        self.prompt = segments;
+
+        self
+    }
+
+    pub fn set_prompt_fn(&mut self, prompt_fn: impl Fn(&Shell) -> String + Send + Sync + 'static) -> &mut Self {
+        self.prompt = vec![PromptSegment::custom(prompt_fn)];
+
+        self
+    }
+
+    pub fn set_right_prompt(&mut self, segments: Vec<PromptSegment>) -> &mut Self {
+        self.right_prompt = segments;
 
         self
     }
@@ -65,9 +210,16 @@ impl Shell {
     pub fn register_help(&mut self) -> &mut Self {
         self.register_command(
         CommandDefinition::new("help")
-            .set_description("- Shows this page")
-            .set_callback(|shell, _args| {
-                shell.help();
+            .add_optional_arg("name", ArgType::Str, "The command to show the details of, or the category to list")
+            .set_description("Shows this page, the details of a command or the commands of a category")
+            .set_category("Shell")
+            .set_callback(|shell, args| {
+                match args.first().map(|name| name.get_str().unwrap()) {
+                    Some(name) if shell.has_command(&name) => shell.help_command(&name),
+                    Some(name) if shell.has_category(&name) => shell.help_category(&name),
+                    Some(name) => shell.help_command(&name), // Reports the unknown command
+                    None => shell.help()
+                }
             })
             .build()
         );
@@ -78,8 +230,9 @@ impl Shell {
     pub fn register_history(&mut self) -> &mut Self {
         self.register_command(
             CommandDefinition::new("history")
-            .add_arg(ArgType::Int)
-            .set_description("len:int - Shows the list of the last len-th commands ran")
+            .add_named_arg("len", ArgType::Int, "How many commands to show, 0 or less shows all of them")
+            .set_description("Shows the list of the last len-th commands ran")
+            .set_category("Shell")
             .set_callback(|shell, args| {
                 let len = args[0].get_int().unwrap();
 
@@ -93,9 +246,90 @@ impl Shell {
     pub fn register_exit(&mut self) -> &mut Self {
         self.register_command(
             CommandDefinition::new("exit")
-            .set_description("- Exits the program")
+            .add_optional_arg("code", ArgType::Int, "The exit code, 0 if omitted")
+            .set_description("Exits the program")
+            .set_category("Shell")
+            .set_callback(|shell, args| {
+                shell.exit_with(args.first().and_then(|code| code.get_int()).unwrap_or(0));
+            })
+            .build()
+        );
+
+        self
+    }
+
+    // Whether run and read_and_run replace the default Ctrl-C behaviour, that ends the process
+    pub fn set_handle_interrupts(&mut self, handle_interrupts: bool) -> &mut Self {
+        self.handle_interrupts = handle_interrupts;
+
+        self
+    }
+
+    // What a second Ctrl-C does while a command is still running, by default it ends the process
+    pub fn set_interrupt_policy(&mut self, policy: InterruptPolicy) -> &mut Self {
+        self.interrupts.set_policy(policy);
+
+        self
+    }
+
+    // Runs when the shell stops, with the exit code, in the order they were added
+    pub fn on_exit(&mut self, hook: impl Fn(&Shell, i32) + Send + Sync + 'static) -> &mut Self {
+        self.exit_hooks.push(Arc::new(hook));
+
+        self
+    }
+
+    pub fn register_jobs(&mut self) -> &mut Self {
+        self.register_command(
+            CommandDefinition::new("jobs")
+            .set_description("Lists the commands started with &")
+            .set_category("Shell")
             .set_callback(|shell, _args| {
-                shell.exit();
+                for job in shell.jobs.lock().unwrap().jobs() {
+                    shell.println(&job.to_string());
+                }
+            })
+            .build()
+        );
+
+        // Polls the jobs instead of joining them, so Ctrl-C stops the waiting, and run_async awaits it without blocking the runtime
+        let mut wait = CommandDefinition::new("wait");
+        wait
+            .add_optional_arg("id", ArgType::Int, "The job to wait for, all of them if omitted")
+            .set_description("Waits for background jobs to finish and shows their output")
+            .set_category("Shell")
+            .set_callback(|shell, args| {
+                let Ok(id) = shell.job_arg(args.first()) else { return };
+
+                while shell.jobs_running(id) && !shell.is_cancelled() {
+                    thread::sleep(JOB_POLL);
+                }
+
+                shell.show_finished_jobs(id);
+            });
+
+        #[cfg(feature = "async")]
+        wait.set_async_callback(|shell, args| Box::pin(async move {
+            let Ok(id) = shell.job_arg(args.first()) else { return };
+
+            while shell.jobs_running(id) && !shell.is_cancelled() { // Without run_async the future isn't dropped on Ctrl-C
+                tokio::time::sleep(JOB_POLL).await;
+            }
+
+            shell.show_finished_jobs(id);
+        }));
+
+        self.register_command(wait.build());
+
+        self.register_command(
+            CommandDefinition::new("kill")
+            .add_named_arg("id", ArgType::Int, "The job to stop")
+            .set_description("Cancels a background job")
+            .set_category("Shell")
+            .set_callback(|shell, args| {
+                if let Ok(Some(id)) = shell.job_arg(args.first()) {
+                    shell.kill_job(id);
+                }
             })
             .build()
         );
@@ -103,6 +337,37 @@ impl Shell {
         self
     }
 
+    // The job id given to a builtin, None if it was omitted, it fails the command if there's no such job
+    fn job_arg(&self, arg: Option<&EvaluatedArg>) -> Result<Option<usize>, ()> {
+        let Some(id) = arg.and_then(|arg| arg.get_int()) else { return Ok(None) };
+
+        match usize::try_from(id).ok().filter(|id| self.job_status(*id).is_some()) {
+            Some(id) => Ok(Some(id)),
+            None => {
+                self.log(LogLevel::ERROR, &format!("There's no job {}", id));
+                self.set_status(1);
+
+                Err(())
+            }
+        }
+    }
+
+    fn jobs_running(&self, id: Option<usize>) -> bool {
+        self.jobs.lock().unwrap().jobs().iter().any(|job| id.is_none_or(|id| job.id() == id) && job.status() == JobStatus::Running)
+    }
+
+    // Joins the jobs that finished and shows their notices and what they wrote
+    fn show_finished_jobs(&self, id: Option<usize>) {
+        if !self.jobs_running(id) {
+            self.wait_jobs(id);
+        }
+
+        for job in self.jobs.lock().unwrap().take_finished() {
+            self.println(&job.to_string());
+            self.print(&job.output().contents().unwrap_or_default());
+        }
+    }
+
     pub fn register_command(&mut self, definition: CommandDefinition) -> &mut Self {
         if !self.command_registry.contains_key(definition.name()) {
             self.command_registry.insert(definition.name().to_string(), definition.clone());
@@ -122,75 +387,805 @@ impl Shell {
         }
     }
 
+    pub fn replace_masks(&self, text: &str) -> String {
+        read::replace_masks(text.to_string(), &self.environment_registry)
+    }
+
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    // Identifies the entries this shell writes to the log file
+    pub fn session_id(&self) -> String {
+        self.logger.lock().unwrap().session_id().to_string()
+    }
+
+    pub fn terminal_log_level(&self) -> LogLevel {
+        self.logger.lock().unwrap().terminal_level()
+    }
+
+    pub fn file_log_level(&self) -> LogLevel {
+        self.logger.lock().unwrap().file_level()
+    }
+
+    // $LOG_LEVEL and $LOG_FILE_LEVEL override the configured thresholds while they hold a valid level
+    fn sync_log_levels(&self) {
+        let terminal = self.get_env_var("LOG_LEVEL").ok();
+        let file = self.get_env_var("LOG_FILE_LEVEL").ok();
+
+        self.logger.lock().unwrap().set_overrides(terminal, file);
+    }
+
     fn set_env(&mut self, name: &str, value: &str) {
         self.environment_registry.insert(name.to_string(), value.to_string());
+
+        if name == "LOG_LEVEL" || name == "LOG_FILE_LEVEL" {
+            self.sync_log_levels();
+        }
     }
 
 
     pub fn register_env_var(&mut self, name: &str, value: &str) -> &mut Self {
-        self.environment_registry.insert(name.to_string(), value.to_string());
+        self.set_env(name, value);
     
         self
     }
 
+    // Reads and runs lines until the user exits or the input ends, then runs the exit hooks and returns the exit code
+    pub fn run(&mut self) -> i32 {
+        let code = loop {
+            if let Some(code) = self.step() {
+                break code;
+            }
+        };
+
+        self.shut_down(code);
+
+        code
+    }
+
+    // Reads and runs a single line, if the shell should stop the process exits, use run to get the control back instead
     pub fn read_and_run(&mut self) {
-        let prompt = self.get_env_var("SYSTEM_PROMPT_DEFINITION").unwrap();
+        if let Some(code) = self.step() {
+            self.shut_down(code);
+
+            process::exit(code)
+        }
+    }
 
-        if prompt != "" {
-            print!("{} ", read::replace_masks(prompt, &self.environment_registry));
-            let _ = io::stdout().flush();
+    // Returns the exit code once the shell should stop
+    fn step(&mut self) -> Option<i32> {
+        if let Some(code) = self.before_prompt() {
+            return Some(code);
         }
 
-        let line = read::read_line();//.trim().to_string();
-        let line = read::replace_masks(line, &self.environment_registry);
+        self.print_prompt();
 
-        
-        self.log(LogLevel::INFO, &format!(">> {}", &line[..line.len()-1]));
+        let line = match read::read_line() {
+            Some(line) => read::replace_masks(line, &self.environment_registry),
+            None => { println!(); return Some(self.last_status); } // The input ended, so the prompt gets its own line
+        };
 
+        self.run_line(&line);
 
-        if line.trim().starts_with("$") { // Verify if it's a environment variable operation
-            let env_var = read::get_env_var(&line);
+        self.after_line(line)
+    }
 
-            match env_var {
-                Ok((name, value)) => { let _ = self.set_env(&name, &value); },
-                Err(e) => self.log(LogLevel::ERROR, &format!("{}", e)),
+    // Sets up the interrupts and the rc file on the first call, returns the exit code if the shell should already stop
+    fn before_prompt(&mut self) -> Option<i32> {
+        if let Some(rc_file) = self.prepare_prompt() {
+            let result = self.source(&rc_file);
+            self.warn_rc_file(&rc_file, result);
+        }
+
+        self.exit_code()
+    }
+
+    #[cfg(feature = "async")]
+    async fn before_prompt_async(&mut self) -> Option<i32> {
+        if let Some(rc_file) = self.prepare_prompt() {
+            let result = self.source_async(&rc_file).await;
+            self.warn_rc_file(&rc_file, result);
+        }
+
+        self.exit_code()
+    }
+
+    // Announces the finished jobs, and returns the rc file if it still has to be run
+    fn prepare_prompt(&mut self) -> Option<String> {
+        if self.handle_interrupts {
+            interrupt::install();
+            self.interrupts.activate();
+        }
+
+        for job in self.jobs.lock().unwrap().take_finished() { // Announced before the prompt is drawn, so they don't break it
+            println!("{}", job);
+        }
+
+        if self.rc_loaded {
+            return None;
+        }
+
+        self.rc_loaded = true;
+        self.rc_file.clone()
+    }
+
+    fn warn_rc_file(&self, rc_file: &str, result: io::Result<()>) {
+        if let Err(e) = result {
+            self.log(LogLevel::WARN, &format!("Couldn't run the rc file {}: {}", rc_file, e));
+        }
+    }
+
+    fn after_line(&mut self, line: String) -> Option<i32> {
+        self.history.push(line);
+
+        if self.do_sparse {
+            println!();
+        }
+
+        self.exit_code()
+    }
+
+    // Like run, but reads the input without blocking the runtime and awaits the async callbacks, Ctrl-C drops their futures
+    #[cfg(feature = "async")]
+    pub async fn run_async(&mut self) -> i32 {
+        use tokio::io::AsyncBufReadExt;
+
+        let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+
+        let code = loop {
+            if let Some(code) = self.before_prompt_async().await {
+                break code;
             }
-        } else {
-            let tokens = read::get_tokens(&line); // Tokenize the read line
-        
-            match tokens {
-                Ok(token) => { // It's a command
-                    match self.command_registry.get(&token.0.0).as_mut() { // Verify is the command is registered
-                        Some(def) => {
-                            match def.instantiate(self, token.1) { // Creates an instance of the command with the given arg list
-                                Ok(inst) => inst.run(), // Runs the instance if it's alright
-                                Err(e) => self.log(LogLevel::ERROR, &format!("{}", e)) // Throws an instantiation error
-                            }
+
+            self.print_prompt();
+
+            let line = loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => break Some(format!("{}\n", line)),
+                    Ok(None) => break None,
+                    Err(e) => { println!("Error: {e}"); break None; }
+                }
+            };
+
+            let line = match line {
+                Some(line) => read::replace_masks(line, &self.environment_registry),
+                None => { println!(); break self.last_status; } // The input ended, so the prompt gets its own line
+            };
+
+            self.run_line_async(&line).await;
+
+            if let Some(code) = self.after_line(line) {
+                break code;
+            }
+        };
+
+        self.shut_down(code);
+
+        code
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_line_async(&mut self, line: &str) {
+        let start = Instant::now();
+        self.log(LogLevel::INFO, &format!(">> {}", line.trim_end()));
+
+        let (command_line, input, output) = match self.redirect(line) {
+            Ok(redirected) => redirected,
+            Err(e) => return self.finish_line(line, start, Err(e), None)
+        };
+
+        let input = mem::replace(&mut self.input, input);
+        let output = mem::replace(&mut self.output, output);
+
+        let (result, command) = self.run_command_line_async(&command_line).await;
+
+        self.input = input;
+        self.output = output;
+
+        self.finish_line(line, start, result, command);
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_command_line_async(&mut self, line: &str) -> (Result<(), ShellError>, Option<LineCommand>) {
+        self.interrupts.start_line();
+
+        if let Some(command_line) = background(line) {
+            return self.start_job(command_line);
+        }
+
+        match self.run_env_line(line) {
+            Some(result) => (result, None),
+            None => {
+                let (instance, command) = self.instantiate_line(line);
+
+                let result = match instance {
+                    Ok(inst) => {
+                        self.interrupts.start_command(self.forced_exit());
+                        let token = self.interrupts.token();
+
+                        tokio::select! {
+                            _ = inst.run_async() => {},
+                            _ = token.cancelled() => {} // Dropping the future stops the command at its current await
                         }
-                        None => { let e = CommandError::UnknownCommand(token.0.0); self.log(LogLevel::ERROR, &format!("{}", e)) } // Throws an unknown command error
-                    }
+
+                        self.interrupts.finish_command();
+                        Ok(())
+                    },
+                    Err(e) => Err(e)
+                };
+
+                (result, command)
+            }
+        }
+    }
+
+    // A second Ctrl-C ends the process from the signal thread while the command keeps the shell
+    // So the exit goes through a copy taken when the command starts, that has the hooks, the variables and the history
+    // The copy is only taken if there are hooks to run and the policy lets the user force the exit, otherwise the exit is just logged
+    fn forced_exit(&self) -> ExitFn {
+        if self.exit_hooks.is_empty() || self.interrupts.policy() == InterruptPolicy::Ignore {
+            let logger = self.logger.clone();
+
+            return Arc::new(move |code| logger.lock().unwrap().write(LogLevel::DEBUG, &format!("Exiting with status {}", code)));
+        }
+
+        let mut snapshot = self.snapshot(self.output.clone());
+        snapshot.exit_hooks = self.exit_hooks.clone();
+        snapshot.history = self.history.clone();
+        snapshot.last_status = self.last_status;
+
+        let snapshot = Mutex::new(snapshot);
+
+        Arc::new(move |code| {
+            let mut shell = snapshot.lock().unwrap();
+
+            shell.exit_with(code);
+            shell.shut_down(code);
+        })
+    }
+
+    fn shut_down(&mut self, code: i32) {
+        self.log(LogLevel::DEBUG, &format!("Exiting with status {}", code));
+
+        for hook in self.exit_hooks.clone() {
+            hook(self, code);
+        }
+
+        *self.exit_code.lock().unwrap() = None; // The shell can be run again
+        self.interrupts.deactivate(); // Ctrl-C belongs to the host program again
+    }
+
+    fn print_prompt(&self) {
+        let left = if self.prompt.is_empty() {
+            let prompt: String = self.get_env_var("SYSTEM_PROMPT_DEFINITION").unwrap_or_default();
+
+            if prompt.is_empty() { prompt } else { format!("{} ", self.replace_masks(&prompt)) }
+        } else {
+            prompt::render(&self.prompt, self)
+        };
+
+        let prompt = if self.right_prompt.is_empty() {
+            left
+        } else {
+            let right = prompt::render(&self.right_prompt, self);
+            let padding = term::width().saturating_sub(style::visible_len(&right));
+
+            format!("{}{}\r{}", " ".repeat(padding), right, left) // Draws the right prompt first and then goes back to the line start
+        };
+
+        print!("{}", prompt);
+        let _ = io::stdout().flush();
+
+        self.interrupts.set_prompt(&prompt);
+    }
+
+    // Runs every non-empty line of a file as if it was typed in, lines starting with # are comments
+    pub fn source(&mut self, path: &str) -> io::Result<()> {
+        let file = File::open(expand_home(path))?;
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            if self.exit_code().is_some() {
+                break;
+            }
+
+            if let Some(line) = self.sourced_line(line) {
+                self.run_line(&line);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Like source, but awaits the async callbacks, so it can be used from inside a runtime
+    #[cfg(feature = "async")]
+    pub async fn source_async(&mut self, path: &str) -> io::Result<()> {
+        let file = File::open(expand_home(path))?;
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            if self.exit_code().is_some() {
+                break;
+            }
+
+            if let Some(line) = self.sourced_line(line) {
+                self.run_line_async(&line).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The line of a sourced file with its variables replaced, None if it's blank or a comment
+    fn sourced_line(&self, line: String) -> Option<String> {
+        if line.trim().is_empty() || line.trim().starts_with('#') {
+            return None;
+        }
+
+        Some(read::replace_masks(line, &self.environment_registry))
+    }
+
+    // Writes the environment registry and the history to a file that can be loaded back with restore
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(expand_home(path))?;
+        let mut names: Vec<&String> = self.environment_registry.keys().collect();
+        names.sort();
+
+        writeln!(file, "[environment]")?;
+        for name in names {
+            writeln!(file, "{}={}", name, self.environment_registry[name])?;
+        }
+
+        writeln!(file, "[history]")?;
+        for line in &self.history {
+            writeln!(file, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+
+    // Loads a file written by save, the restored history goes before the current session one
+    pub fn restore(&mut self, path: &str) -> io::Result<()> {
+        let file = File::open(expand_home(path))?;
+        let mut section = String::new();
+        let mut history = vec![];
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            if line == "[environment]" || line == "[history]" {
+                section = line;
+                continue;
+            }
+
+            match section.as_str() {
+                "[environment]" => match line.split_once('=') {
+                    Some((name, value)) => self.set_env(name, value),
+                    None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} isn't a proper environment variable entry", line)))
+                },
+                "[history]" => history.push(format!("{}\n", line)),
+                _ => if !line.trim().is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is outside of a section", line)));
                 }
-                Err(e) => self.log(LogLevel::ERROR, &format!("{}", e)), // Throws an invalid input error
             }
         }
 
+        history.append(&mut self.history);
+        self.history = history;
 
-        self.history.push(line.clone());
+        Ok(())
+    }
 
-        if self.do_sparse {
-            println!("");
+    // The line is logged before it runs, so the entries its command writes come after it
+    fn run_line(&mut self, line: &str) {
+        let start = Instant::now();
+        self.log(LogLevel::INFO, &format!(">> {}", line.trim_end()));
+
+        let (command_line, input, output) = match self.redirect(line) {
+            Ok(redirected) => redirected,
+            Err(e) => return self.finish_line(line, start, Err(e), None) // The files are checked before the command starts
+        };
+
+        let input = mem::replace(&mut self.input, input);
+        let output = mem::replace(&mut self.output, output);
+
+        let (result, command) = self.run_command_line(&command_line);
+
+        self.input = input;
+        self.output = output;
+
+        self.finish_line(line, start, result, command);
+    }
+
+    // Opens the files of the redirections in the line, and returns the line without them along with the handles the command uses
+    // Without redirections, or in an environment variable line, those are the current ones
+    fn redirect(&self, line: &str) -> Result<(String, Input, Output), ShellError> {
+        if line.trim().starts_with('$') {
+            return Ok((line.to_string(), self.input.clone(), self.output.clone()));
+        }
+
+        let (command_line, redirections) = read::get_redirections(line)?;
+        let mut input = self.input.clone();
+        let mut output = self.output.clone();
+
+        for redirection in redirections {
+            let path = expand_home(redirection.path());
+
+            let file = match &redirection {
+                Redirection::Write(_, _) => File::create(&path),
+                Redirection::Append(_, _) => OpenOptions::new().create(true).append(true).open(&path),
+                Redirection::Read(_, _) => File::open(&path)
+            };
+
+            let file = match file {
+                Ok(file) => file,
+                Err(e) => return Err(InputError::RedirectionFile(redirection.path().to_string(), e, redirection.span()).into())
+            };
+
+            match redirection {
+                Redirection::Read(_, _) => input = Input::file(file),
+                _ => output = Output::file(file)
+            }
+        }
+
+        Ok((command_line, input, output))
+    }
+
+    fn run_command_line(&mut self, line: &str) -> (Result<(), ShellError>, Option<LineCommand>) {
+        self.interrupts.start_line();
+
+        if let Some(command_line) = background(line) {
+            return self.start_job(command_line);
+        }
+
+        match self.run_env_line(line) {
+            Some(result) => (result, None),
+            None => {
+                let (instance, command) = self.instantiate_line(line);
+
+                let result = instance.and_then(|inst| { // Runs the instance if it's alright, Ctrl-C cancels its token instead of ending the process
+                    self.interrupts.start_command(self.forced_exit());
+                    let result = inst.run();
+                    self.interrupts.finish_command();
+
+                    result.map_err(ShellError::from)
+                });
+
+                (result, command)
+            }
+        }
+    }
+
+    // Runs the command of the line on a thread of its own, the errors in the line are reported right away
+    fn start_job(&self, line: &str) -> (Result<(), ShellError>, Option<LineCommand>) {
+        let (instance, command) = self.instantiate_line(line);
+
+        if let Err(e) = instance {
+            return (Err(e), command);
+        }
+
+        let output = if self.output.is_terminal() { Output::buffer() } else { self.output.clone() }; // It keeps writing to the redirected file
+        let log_output = if self.output.is_terminal() { Some(output.clone()) } else { None }; // So the entries don't break the prompt
+        let snapshot = self.snapshot(output.clone());
+        let mut job = Job::new(line, snapshot.cancellation_token(), output);
+        let status = job.status_handle();
+        let started = job.line().to_string();
+        let line = line.to_string();
+
+        job.set_handle(thread::spawn(move || {
+            log::redirect_terminal(log_output);
+
+            let run = || match snapshot.instantiate_line(&line).0 {
+                Ok(inst) => match inst.run() {
+                    Ok(()) if snapshot.is_cancelled() => JobStatus::Killed,
+                    Ok(()) => JobStatus::Done(snapshot.take_status()),
+                    Err(e) => { snapshot.log(LogLevel::ERROR, &format!("{}: {}", e.code(), e)); JobStatus::Done(1) }
+                },
+                Err(e) => { snapshot.log(LogLevel::ERROR, &format!("{}: {}", e.code(), e)); JobStatus::Done(1) }
+            };
+
+            // A panic would otherwise leave the job running forever, it gets the status of a Rust program that panics
+            let result = panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(|_| {
+                snapshot.log(LogLevel::ERROR, &format!("The job {} panicked", line.trim_end()));
+                JobStatus::Done(101)
+            });
+
+            *status.lock().unwrap() = result;
+        }));
+
+        let id = self.jobs.lock().unwrap().add(job);
+        self.println(&format!("[{}] {}", id, started));
+
+        (Ok(()), command)
+    }
+
+    // Sets the variable if the line is an environment variable operation, None if it's a command
+    fn run_env_line(&mut self, line: &str) -> Option<Result<(), ShellError>> {
+        if !line.trim().starts_with('$') {
+            return None;
+        }
+
+        Some(match read::get_env_var(line) {
+            Ok((name, value)) => {
+                if (name == "LOG_LEVEL" || name == "LOG_FILE_LEVEL") && value.parse::<LogLevel>().is_err() {
+                    self.log(LogLevel::WARN, &format!("{} isn't a log level, {} will be ignored", value, name));
+                }
+
+                self.set_env(&name, &value);
+                Ok(())
+            },
+            Err(e) => Err(e.into()),
+        })
+    }
+
+    // Builds the command of the line, along with its name and the values of its arguments, None if it can't be built
+    fn instantiate_line(&self, line: &str) -> (Result<CommandInstance<'_>, ShellError>, Option<LineCommand>) {
+        if let Some(program) = self.instantiate_program(line) {
+            return program;
+        }
+
+        let (identifier, args) = match read::get_tokens(line) { // Tokenize the read line
+            Ok(tokens) => tokens,
+            Err(e) => return (Err(e.into()), None) // Throws an invalid input error
+        };
+
+        let instance = match self.command_registry.get(&identifier.0) { // Verify is the command is registered
+            Some(def) => {
+                let no_args = args.is_empty();
+
+                match def.instantiate(self, args) { // Creates an instance of the command with the given arg list
+                    Ok(inst) => Ok(inst),
+                    Err(CommandError::TooFewArguments { command, expected, got, missing, .. }) if no_args => { // Points right after the command name
+                        Err(CommandError::TooFewArguments { command, expected, got, missing, span: identifier.1.after() }.into())
+                    },
+                    Err(e) => Err(e.into()) // Throws an instantiation error
+                }
+            }
+            None => Err(self.unknown_command(&identifier.0, identifier.1).into()) // Throws an unknown command error
+        };
+
+        let command = instance.as_ref().ok().map(|inst| (identifier.0.clone(), inst.arg_list().iter().map(|arg| arg.to_string()).collect()));
+
+        (instance, command)
+    }
+
+    // Builds a command that runs a program of the host, None if the line is left to the registered commands
+    // That's the case if external commands are off, the command is registered or no program has its name, unless it starts with !
+    fn instantiate_program(&self, line: &str) -> Option<(Result<CommandInstance<'_>, ShellError>, Option<LineCommand>)> {
+        if !self.external_commands {
+            return None;
+        }
+
+        let mut words = read::get_words(line);
+
+        if words.is_empty() {
+            return None;
+        }
+
+        let (name, span) = words.remove(0);
+        let forced = name.starts_with('!');
+        let name = name.trim_start_matches('!').to_string();
+
+        if !forced && (self.command_registry.contains_key(&name) || name.starts_with('$')) {
+            return None;
+        }
+
+        let args: Vec<String> = words.into_iter().map(|(word, _)| word).collect();
+        let command = Some((name.clone(), args.clone()));
+
+        let path = match external::find_program(&name) {
+            Some(path) => path,
+            None if forced => return Some((Err(CommandError::ProgramNotFound { name, span }.into()), None)),
+            None => return None // The unknown command error is more helpful, it has suggestions
+        };
+
+        let callback = Arc::new(move |shell: &Shell, _: &ArgList| {
+            match external::run_program(&path, &args, &shell.environment_registry, &shell.input, &shell.output, &shell.cancellation_token()) {
+                Ok(status) => shell.set_status(status),
+                Err(e) => {
+                    shell.log(LogLevel::ERROR, &format!("Couldn't run {}: {}", path.display(), e));
+                    shell.set_status(126);
+                }
+            }
+        });
+
+        Some((Ok(CommandInstance::new(self, &name, ArgList::new(vec![], vec![]), callback)), command))
+    }
+
+    // Sets the status and logs how the command went, or the error if the line couldn't run
+    fn finish_line(&mut self, line: &str, start: Instant, result: Result<(), ShellError>, command: Option<LineCommand>) {
+        let status = self.take_status();
+
+        self.last_status = match result {
+            Ok(()) if self.is_cancelled() => 130,
+            Ok(()) => status,
+            Err(_) => 1
+        };
+
+        if let Some((name, args)) = command {
+            let message = format!("<< {}", name);
+            let record = CommandRecord { name, args, status: self.last_status, duration: start.elapsed() };
+
+            self.logger.lock().unwrap().write_command(LogLevel::INFO, &message, &record);
+        }
+
+        if let Err(e) = result {
+            self.log(LogLevel::ERROR, &format!("{}: {}", e.code(), e));
+
+            if let Some(span) = e.span() {
+                self.print_diagnostic(line, span);
+            }
         }
     }
 
+    // Prints the line with the span underlined, so the user can see where the error is
+    fn print_diagnostic(&self, line: &str, span: Span) {
+        let diagnostic = read::caret_diagnostic(line, span);
+        let (text, carets) = diagnostic.split_once('\n').unwrap_or((&diagnostic, ""));
+        let style = {
+            let logger = self.logger.lock().unwrap();
+
+            if logger.color_mode().enabled(Stream::Stderr) { logger.theme().log_style(&LogLevel::ERROR) } else { Style::new() }
+        };
+
+        eprintln!("  {}\n  {}", text, style.paint(carets));
+    }
+
     pub fn log(&self, log_level: LogLevel, message: &str) {
-        if let Some(file) = &self.log_file {
-            log::log(file, log_level, message);
+        self.logger.lock().unwrap().write(log_level, message);
+    }
+
+    // Makes the records of the log crate go through this shell's logger, it can only be done once per program
+    #[cfg(feature = "log")]
+    pub fn install_logger(&self) -> Result<(), ::log::SetLoggerError> {
+        ::log::set_boxed_logger(Box::new(crate::inout::facade::ShellLogger::new(self.logger.clone())))?;
+        ::log::set_max_level(::log::LevelFilter::Trace); // The thresholds can change at runtime, so the filtering is left to the logger
+
+        Ok(())
+    }
+
+    // Writes to the output of the command, that is the terminal unless it runs in the background
+    pub fn print(&self, text: &str) {
+        self.output.write_str(text);
+    }
+
+    pub fn println(&self, text: &str) {
+        self.output.write_str(&format!("{}\n", text));
+    }
+
+    // Where the command reads from, the terminal unless the line redirects a file into it
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    // Applies the style to a text that will be printed to the output, unless colors are disabled or it isn't the terminal
+    pub fn paint(&self, style: &Style, text: &str) -> String {
+        if self.output.is_terminal() && self.logger.lock().unwrap().color_mode().enabled(Stream::Stdout) {
+            style.paint(text)
+        } else {
+            text.to_string()
         }
     }
 
+    pub fn theme(&self) -> Theme {
+        self.logger.lock().unwrap().theme().clone()
+    }
+
+    // Lists every command with its usage and description, grouped by category, with the categories and the commands in alphabetical order
     pub fn help(&self) {
-        for def in self.command_registry.values() {
-            println!("{} {}", def.name(), def.description())
+        for (i, category) in self.categories().into_iter().enumerate() {
+            if i > 0 {
+                self.println("");
+            }
+
+            self.println(&format!("{}:", if category.is_empty() { "Other" } else { category }));
+            self.print_commands(category, 2);
+        }
+    }
+
+    // Lists the commands of a single category, the name isn't case sensitive
+    pub fn help_category(&self, category: &str) {
+        match self.find_category(category) {
+            Some(category) => self.print_commands(category, 0),
+            None => self.log(LogLevel::ERROR, &format!("{} isn't a category", category))
+        }
+    }
+
+    // The categories of the registered commands in alphabetical order, the commands without one come last under ""
+    // The names aren't case sensitive, so the ones that only differ in case are listed once, and Other is the same as ""
+    pub fn categories(&self) -> Vec<&'static str> {
+        let mut categories: Vec<&'static str> = self.command_registry.values()
+            .map(|def| if category_key(def.category()).is_empty() { "" } else { def.category() })
+            .collect();
+        categories.sort_by_key(|category| (category.is_empty(), category.to_lowercase(), *category));
+        categories.dedup_by_key(|category| category.to_lowercase());
+
+        categories
+    }
+
+    pub fn has_command(&self, name: &str) -> bool {
+        self.command_registry.contains_key(name)
+    }
+
+    pub fn has_category(&self, category: &str) -> bool {
+        self.find_category(category).is_some()
+    }
+
+    // The registered category with the name, Other stands for the commands without one
+    fn find_category(&self, category: &str) -> Option<&'static str> {
+        self.categories().into_iter().find(|name| category_key(name) == category_key(category))
+    }
+
+    fn print_commands(&self, category: &str, indent: usize) {
+        let mut definitions: Vec<&CommandDefinition> = self.command_registry.values().filter(|def| category_key(def.category()) == category_key(category)).collect();
+        definitions.sort_by_key(|def| def.name());
+
+        for def in definitions {
+            let line = if def.description().is_empty() { def.usage() } else { format!("{} - {}", def.usage(), def.description()) };
+
+            for (i, wrapped) in term::wrap(&line, term::width().saturating_sub(indent), 4).iter().enumerate() {
+                match i {
+                    0 => self.println(&format!("{}{}{}", " ".repeat(indent), self.paint(&self.theme().command_style(), def.name()), &wrapped[def.name().len()..])),
+                    _ => self.println(&format!("{}{}", " ".repeat(indent), wrapped))
+                }
+            }
+        }
+    }
+
+    fn unknown_command(&self, name: &str, span: Span) -> CommandError {
+        let suggestions = suggest::suggestions(name, self.command_registry.keys().map(|key| key.as_str()));
+
+        CommandError::UnknownCommand { name: name.to_string(), suggestions, span }
+    }
+
+    // Shows the detailed page of a command, with its usage and the description of every argument
+    pub fn help_command(&self, name: &str) {
+        let def = match self.command_registry.get(name) {
+            Some(def) => def,
+            None => {
+                let error = self.unknown_command(name, Span::default());
+
+                return self.log(LogLevel::ERROR, &format!("{}: {}", error.code(), error));
+            }
+        };
+
+        let width = term::width();
+
+        self.println(&format!("Usage: {}{}", self.paint(&self.theme().command_style(), def.name()), &def.usage()[def.name().len()..]));
+
+        if !def.description().is_empty() {
+            self.println("");
+            for line in term::wrap(def.description(), width, 0) {
+                self.println(&line);
+            }
+        }
+
+        if !def.arg_list().is_empty() {
+            let column = def.arg_list().iter().map(|arg| arg.usage().len()).max().unwrap_or(0) + 4;
+
+            self.println("");
+            self.println("Arguments:");
+
+            for arg in def.arg_list() {
+                let lines = term::wrap(arg.description(), width.saturating_sub(column), 0);
+
+                self.println(&format!("  {:<usage_width$}{}", arg.usage(), lines[0], usage_width = column - 2));
+                for line in &lines[1..] {
+                    self.println(&format!("{}{}", " ".repeat(column), line));
+                }
+            }
         }
     }
 
@@ -205,12 +1200,107 @@ impl Shell {
         };
 
         for i in (self.history.len() - len)..self.history.len() {
-            print!("{}: {}", i, self.history[i]);
+            self.print(&format!("{}: {}", i, self.history[i]));
         }
     }
     
+    // Sets the exit status of the running command, it's 0 if the callback doesn't set one
+    pub fn set_status(&self, status: i32) {
+        *self.command_status.lock().unwrap() = Some(status);
+    }
+
+    fn take_status(&self) -> i32 {
+        self.command_status.lock().unwrap().take().unwrap_or(0)
+    }
+
+    // The token of the running command, it's cancelled when the user presses Ctrl-C
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.interrupts.token()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.interrupts.token().is_cancelled()
+    }
+
+    // Lets other threads interrupt the shell as Ctrl-C does
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupts.clone()
+    }
+
+    pub fn job_status(&self, id: usize) -> Option<JobStatus> {
+        self.jobs.lock().unwrap().get(id).map(|job| job.status())
+    }
+
+    // What the job wrote so far
+    pub fn job_output(&self, id: usize) -> Option<String> {
+        self.jobs.lock().unwrap().get(id).and_then(|job| job.output().contents())
+    }
+
+    // Blocks until the job, or every job, finishes
+    pub fn wait_jobs(&self, id: Option<usize>) {
+        let handles = self.jobs.lock().unwrap().take_handles(id);
+
+        for handle in handles {
+            let _ = handle.join();
+        }
+    }
+
+    // Cancels the token of the job, returns false if there's no such job
+    pub fn kill_job(&self, id: usize) -> bool {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(job) => { job.kill(); true },
+            None => false
+        }
+    }
+
+    // Asks the shell to stop after the current line, run returns the code
     pub fn exit(&self) {
-        process::exit(0)
+        self.exit_with(0)
+    }
+
+    pub fn exit_with(&self, code: i32) {
+        *self.exit_code.lock().unwrap() = Some(code);
+    }
+
+    // The code the shell will exit with, if it was asked to
+    pub fn exit_code(&self) -> Option<i32> {
+        *self.exit_code.lock().unwrap()
+    }
+
+}
+
+// The line without the trailing &, if it asks to run in the background
+fn background(line: &str) -> Option<&str> {
+    let command = line.trim_end().strip_suffix('&')?;
+
+    if command.matches('"').count() % 2 == 1 || command.trim().is_empty() { // The & is quoted, or there's no command
+        return None;
+    }
+
+    Some(command)
+}
+
+// What two category names are compared by, the case is ignored and Other means no category
+fn category_key(category: &str) -> String {
+    if category.eq_ignore_ascii_case("Other") { String::new() } else { category.to_lowercase() }
+}
+
+// Replaces a leading ~ with the user's home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => PathBuf::from(format!("{}{}", home, rest)),
+        _ => PathBuf::from(path)
     }
+}
 
+impl Drop for Shell {
+    fn drop(&mut self) {
+        self.interrupts.deactivate();
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
 }
\ No newline at end of file
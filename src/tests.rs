@@ -1,6 +1,36 @@
-use chrono::Local;
+use std::{env, fs, sync::atomic::{AtomicUsize, Ordering}, time::Duration};
 
-use crate::{shell::Shell, commands::{definition::CommandDefinition, argument::ArgType}, inout::log::LogLevel};
+use chrono::{Local, NaiveDate};
+use diysh_derive::FromArgs;
+
+use crate::{shell::Shell, jobs::JobStatus, error::{CommandError, ConfigError, EnvVarError, InputError, ShellError}, commands::{suggest, definition::CommandDefinition, argument::{ArgList, ArgType, CustomArgType, EvaluatedArg}, typed::FromArgs, validator::Validator}, inout::{interrupt::{CancellationToken, InterruptPolicy}, log::{LogFallback, LogFormat, LogLevel, Retention, Rotation}, prompt::{self, PromptSegment}, style::{self, Color, Style}, read::{self, ArgToken, Span}, term}};
+
+fn literal(text: &str) -> ArgToken {
+    ArgToken::Literal(text.to_string(), Span::default())
+}
+
+// A path in the temporary directory, the process id keeps parallel runs apart
+fn temp_path(name: &str) -> String {
+    env::temp_dir().join(format!("diysh-{}-{}", std::process::id(), name)).to_string_lossy().to_string()
+}
+
+// Writes a file to the temporary directory and returns its path
+fn temp_file(name: &str, content: &str) -> String {
+    let path = temp_path(name);
+    fs::write(&path, content).unwrap();
+
+    path
+}
+
+// Runs the lines as if they were typed in, through a file of their own since run_line is private to the shell
+fn run_lines(shell: &mut Shell, lines: &str) {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let path = temp_file(&format!("lines-{}.rc", COUNT.fetch_add(1, Ordering::SeqCst)), lines);
+    shell.source(&path).unwrap();
+
+    let _ = fs::remove_file(path);
+}
 
 #[test]
 fn time() {
@@ -14,15 +44,16 @@ fn shell_test() {
     shell
         .set_sparse(true)
         .set_prompt("$USER$ ~>>")
-        .set_log_directory("/tmp/diysh/")
+        .set_log_directory(&env::temp_dir().join("diysh").to_string_lossy()).unwrap()
+        .set_rc_file(&temp_file("shell-test.rc", "help\nsum 1 2\nprint \"Hello World\"\n$TO_PRINT=hi\nechoEnv\nexit\n"))
         .register_env_var("USER", "ojarrisonn_")
         .register_help()
         .register_history()
         .register_exit()
         
         .register_command( CommandDefinition::new("print")
-            .set_description("text:str - Prints the specified text to the terminal")
-            .add_arg(ArgType::Str)
+            .set_description("Prints the specified text to the terminal")
+            .add_named_arg("text", ArgType::Str, "The text to print")
             .set_callback(|shell, args| {
                 let text = args[0].get_str().unwrap();
 
@@ -32,9 +63,9 @@ fn shell_test() {
         )
         
         .register_command( CommandDefinition::new("sum")
-            .set_description("a:int b:int - Prints the result of the sum of a + b")
-            .add_arg(ArgType::Int)
-            .add_arg(ArgType::Int)
+            .set_description("Prints the result of the sum of a + b")
+            .add_named_arg("a", ArgType::Int, "The first term")
+            .add_named_arg("b", ArgType::Int, "The second term")
             .set_callback(|shell, args| {
                 let a = args[0].get_int().unwrap();
                 let b = args[1].get_int().unwrap();
@@ -45,7 +76,7 @@ fn shell_test() {
         )
         
         .register_command(CommandDefinition::new("echoEnv")
-            .set_description("- Prints the value stored in $TO_PRINT")
+            .set_description("Prints the value stored in $TO_PRINT")
             .set_callback(|shell, _args| {
                 match shell.get_env_var::<String>("TO_PRINT") {
                     Ok(text) => { println!("{}", text); shell.log(LogLevel::INFO, &text)},
@@ -56,7 +87,902 @@ fn shell_test() {
     
         );
 
-    loop {
-        shell.read_and_run();
+    assert_eq!(shell.run(), 0); // The rc file exits before the first prompt, so the terminal is never read
+    assert_eq!(shell.history_len(), 0);
+}
+#[test]
+fn source_test() {
+    let path = temp_file("source-test.rc", "# comment\n$GREETING=hello\n\n$TARGET=$GREETING$ world\n");
+
+    let mut shell = Shell::new();
+    shell.source(&path).unwrap();
+
+    assert_eq!(shell.get_env_var::<String>("GREETING").unwrap(), "hello");
+    assert_eq!(shell.get_env_var::<String>("TARGET").unwrap(), "hello world");
+}
+
+#[test]
+fn save_restore_test() {
+    let path = &temp_path("save-restore-test.session");
+
+    let mut shell = Shell::new();
+    shell
+        .register_env_var("USER", "ojarrisonn_")
+        .register_env_var("YEAR", "2023");
+    shell.save(path).unwrap();
+
+    let mut restored = Shell::new();
+    restored.restore(path).unwrap();
+
+    assert_eq!(restored.get_env_var::<String>("USER").unwrap(), "ojarrisonn_");
+    assert_eq!(restored.get_env_var::<i32>("YEAR").unwrap(), 2023);
+}
+
+#[test]
+fn prompt_segments_test() {
+    let mut shell = Shell::new();
+    shell.register_env_var("USER", "ojarrisonn_");
+
+    let connection = String::from("db@localhost"); // The closures can capture the state of the app
+    let segments = vec![
+        PromptSegment::Text("$USER$ ".to_string()),
+        PromptSegment::Status,
+        PromptSegment::Text(":".to_string()),
+        PromptSegment::HistoryNumber,
+        PromptSegment::custom(move |_shell| format!(" {} ~>> ", connection))
+    ];
+
+    assert_eq!(prompt::render(&segments, &shell), "ojarrisonn_ 0:0 db@localhost ~>> ");
+}
+
+#[test]
+fn style_test() {
+    let style = Style::new().set_foreground(Color::Red).set_bold(true).build();
+    let painted = style.paint("error");
+
+    assert_eq!(painted, "\x1b[1;31merror\x1b[0m");
+    assert_eq!(style::visible_len(&painted), 5);
+    assert_eq!(Style::new().paint("plain"), "plain");
+}
+
+#[test]
+fn arg_types_test() {
+    let evaluate = |arg_type: ArgType, value: &str| arg_type.evaluate(&literal(value));
+
+    assert_eq!(evaluate(ArgType::Long, "-9000000000").unwrap().get_long(), Some(-9_000_000_000));
+    assert_eq!(evaluate(ArgType::ULong, "18000000000000000000").unwrap().get_ulong(), Some(18_000_000_000_000_000_000));
+    assert_eq!(evaluate(ArgType::Char, "x").unwrap().get_char(), Some('x'));
+    assert_eq!(evaluate(ArgType::Duration, "1h30m").unwrap().get_duration(), Some(Duration::from_secs(5400)));
+    assert_eq!(evaluate(ArgType::Duration, "250ms").unwrap().get_duration(), Some(Duration::from_millis(250)));
+    assert_eq!(evaluate(ArgType::Bytes, "10MB").unwrap().get_bytes(), Some(10_000_000));
+    assert_eq!(evaluate(ArgType::Bytes, "2KiB").unwrap().get_bytes(), Some(2048));
+    assert_eq!(evaluate(ArgType::IpAddr, "127.0.0.1").unwrap().get_ip_addr(), Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(evaluate(ArgType::DateTime, "2023-07-14").unwrap().get_date_time(), NaiveDate::from_ymd_opt(2023, 7, 14).unwrap().and_hms_opt(0, 0, 0));
+    assert_eq!(evaluate(ArgType::Enum(vec!["start", "stop"]), "stop").unwrap().get_enum(), Some("stop".to_string()));
+
+    assert!(evaluate(ArgType::Duration, "5 parsecs").is_err());
+    assert!(evaluate(ArgType::Duration, "99999999999999999999d").is_err());
+    assert!(evaluate(ArgType::Bytes, "99999999999999999999TB").is_err());
+    assert!(evaluate(ArgType::Bytes, "10XB").is_err());
+
+    let error = evaluate(ArgType::Enum(vec!["start", "stop"]), "restart").unwrap_err();
+    assert_eq!(format!("{}", error), "Mismatch argument type, can convert restart into Enum(start|stop), did you mean start?");
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct HexColor(u8, u8, u8);
+
+impl CustomArgType for HexColor {
+    fn type_name() -> String {
+        "HexColor".to_string()
+    }
+
+    fn parse(token: &ArgToken) -> Option<Self> {
+        let text = token.to_string();
+        let hex = text.strip_prefix('#')?;
+
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+        Some(HexColor(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    fn completions() -> Vec<String> {
+        vec!["#000000".to_string(), "#ffffff".to_string()]
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn custom_arg_type_test() {
+    let arg_type = ArgType::custom::<HexColor>();
+
+    let color = arg_type.evaluate(&literal("#ff8000")).unwrap();
+    assert_eq!(color.get_custom::<HexColor>(), Some(HexColor(255, 128, 0)));
+    assert_eq!(color.get_str(), None);
+
+    let error = arg_type.evaluate(&literal("orange")).unwrap_err();
+    assert_eq!(format!("{}", error), "Mismatch argument type, can convert orange into HexColor");
+
+    assert_eq!(arg_type.completions(), vec!["#000000", "#ffffff"]);
+}
+
+#[test]
+fn list_and_tuple_test() {
+    let tags = ArgType::list(ArgType::Str, ',').evaluate(&ArgToken::Literal("a,b,c".to_string(), Span::default())).unwrap().get_list().unwrap();
+    assert_eq!(tags.iter().map(|tag| tag.get_str().unwrap()).collect::<Vec<String>>(), vec!["a", "b", "c"]);
+
+    let point = ArgType::tuple(vec![ArgType::Int, ArgType::Int], ',').evaluate(&ArgToken::Literal("10,20".to_string(), Span::default())).unwrap().get_tuple().unwrap();
+    assert_eq!((point[0].get_int(), point[1].get_int()), (Some(10), Some(20)));
+
+    let error = ArgType::list(ArgType::Int, ';').evaluate(&literal("1;two;3")).unwrap_err();
+    assert_eq!(format!("{}", error), "Mismatch argument type, can convert two into Int");
+
+    let error = ArgType::tuple(vec![ArgType::Int, ArgType::Int], ',').evaluate(&ArgToken::Literal("10,20,30".to_string(), Span::default())).unwrap_err();
+    assert_eq!(format!("{}", error), "Mismatch argument type, can convert 10,20,30 into (Int,Int)");
+}
+
+#[derive(FromArgs)]
+struct MoveArgs {
+    name: String,
+    steps: i32,
+    tags: Vec<String>
+}
+
+#[test]
+fn typed_args_test() {
+    assert_eq!(MoveArgs::arg_types().len(), 3);
+
+    let args = vec![
+        EvaluatedArg::Str("player".to_string()),
+        EvaluatedArg::Int(3),
+        ArgType::list(ArgType::Str, ',').evaluate(&ArgToken::Literal("fast,quiet".to_string(), Span::default())).unwrap()
+    ];
+    let move_args = MoveArgs::from_args(&args).unwrap();
+
+    assert_eq!(move_args.name, "player");
+    assert_eq!(move_args.steps, 3);
+    assert_eq!(move_args.tags, vec!["fast", "quiet"]);
+
+    assert!(MoveArgs::from_args(&args[..2]).is_none());
+
+    let definition = CommandDefinition::new("move")
+        .set_typed_callback(|_shell, _args: MoveArgs| {})
+        .build();
+    assert_eq!(definition.arg_list().len(), 3);
+}
+
+// A hand-written FromArgs whose from_args disagrees with its arg_types
+struct Broken;
+
+impl FromArgs for Broken {
+    fn arg_types() -> Vec<ArgType> {
+        vec![ArgType::Int]
+    }
+
+    fn from_args(_args: &[EvaluatedArg]) -> Option<Self> {
+        None
+    }
+}
+
+#[test]
+fn typed_args_mismatch_test() {
+    let mut shell = Shell::new();
+    shell.set_terminal_log_level(LogLevel::ERROR);
+
+    let definition = CommandDefinition::new("broken")
+        .set_typed_callback(|_shell, _args: Broken| {})
+        .build();
+
+    definition.instantiate(&shell, vec![literal("1")]).unwrap().run().unwrap(); // Logs the error instead of panicking
+}
+
+#[test]
+fn validators_test() {
+    let shell = Shell::new();
+    let definition = CommandDefinition::new("connect")
+        .add_arg(ArgType::Str)
+        .add_validator(Validator::regex("[a-z]+(\\.[a-z]+)*"))
+        .add_arg(ArgType::Int)
+        .add_validator(Validator::range(1..=65535))
+        .add_validator(Validator::predicate(|arg| arg.get_int() != Some(22), "anything but 22"))
+        .build();
+
+    let tokens = |host: &str, port: &str| vec![literal(host), literal(port)];
+
+    assert!(definition.instantiate(&shell, tokens("example.com", "8080")).is_ok());
+
+    let error = definition.instantiate(&shell, tokens("example.com", "70000")).err().unwrap();
+    assert_eq!(format!("{}", error), "Invalid argument #2 for connect, it must be between 1 and 65535");
+
+    let error = definition.instantiate(&shell, tokens("example.com", "22")).err().unwrap();
+    assert_eq!(format!("{}", error), "Invalid argument #2 for connect, it must be anything but 22");
+
+    let error = definition.instantiate(&shell, tokens("Example!", "80")).err().unwrap();
+    assert_eq!(format!("{}", error), "Invalid argument #1 for connect, it must be matching [a-z]+(\\.[a-z]+)*");
+
+    let definition = CommandDefinition::new("seek")
+        .add_arg(ArgType::ULong)
+        .add_validator(Validator::range(1..u64::MAX))
+        .build();
+
+    assert!(definition.instantiate(&shell, vec![literal("18446744073709551614")]).is_ok()); // Too close to the bound for a f64
+    assert!(definition.instantiate(&shell, vec![literal("18446744073709551615")]).is_err());
+    assert_eq!(definition.arg_types().len(), 1);
+}
+
+#[test]
+fn usage_test() {
+    let shell = Shell::new();
+    let definition = CommandDefinition::new("history")
+        .add_named_arg("len", ArgType::Int, "How many commands to show")
+        .add_optional_arg("filter", ArgType::Str, "Only shows the commands containing it")
+        .build();
+
+    assert_eq!(definition.usage(), "history <len:Int> [filter:Str]");
+    assert_eq!(CommandDefinition::new("sum").add_arg(ArgType::Int).build().usage(), "sum <Int>");
+
+    assert!(definition.instantiate(&shell, vec![literal("10")]).is_ok());
+    assert!(definition.instantiate(&shell, vec![literal("10"), literal("print")]).is_ok());
+    assert!(definition.instantiate(&shell, vec![]).is_err());
+
+    assert_eq!(term::wrap("Shows the list of the last commands ran", 16, 2), vec!["Shows the list", "  of the last", "  commands ran"]);
+}
+
+#[test]
+fn named_args_test() {
+    let shell = Shell::new();
+    let definition = CommandDefinition::new("sum")
+        .add_named_arg("a", ArgType::Int, "The first term")
+        .add_named_arg("b", ArgType::Int, "The second term")
+        .build();
+
+    let error = definition.instantiate(&shell, vec![literal("1")]).err().unwrap();
+    assert_eq!(format!("{}", error), "Too few arguments for sum, expected 2, but got 1, missing b");
+
+    let error = definition.instantiate(&shell, vec![literal("1"), literal("two")]).err().unwrap();
+    assert_eq!(format!("{}", error), "Mismatch argument type for b, can convert two into Int");
+
+    let args = ArgList::new(vec![EvaluatedArg::Int(1), EvaluatedArg::Int(2)], vec!["a", "b"]);
+    assert_eq!(args.named("b").unwrap().get_int(), Some(2));
+    assert_eq!(args[0].get_int(), Some(1));
+    assert!(args.named("c").is_none());
+}
+
+#[test]
+fn options_test() {
+    let flag = |name: &str| ArgToken::Flag(name.to_string(), Span::default());
+
+    let shell = Shell::new();
+    let definition = CommandDefinition::new("connect")
+        .add_named_arg("host", ArgType::Str, "Where to connect")
+        .add_optional_arg("port", ArgType::Int, "The port")
+        .add_optional_arg("verbose", ArgType::Bool, "Prints every message")
+        .build();
+
+    assert!(definition.instantiate(&shell, vec![flag("port"), literal("80"), literal("example.com")]).is_ok()); // Options go anywhere
+    assert!(definition.instantiate(&shell, vec![literal("example.com"), literal("80"), flag("verbose")]).is_ok());
+
+    let error = definition.instantiate(&shell, vec![literal("example.com"), flag("prot"), literal("80")]).err().unwrap();
+    match &error {
+        CommandError::UnknownArgument { name, suggestions, .. } => { assert_eq!(name, "prot"); assert_eq!(suggestions, &vec!["port"]); },
+        _ => panic!("Expected an UnknownArgument, got {}", error)
+    }
+    assert_eq!(format!("{}", error), "Unknown argument --prot for connect, did you mean --port?");
+
+    let error = definition.instantiate(&shell, vec![literal("example.com"), flag("port")]).err().unwrap();
+    assert_eq!(format!("{}", error), "Too few arguments for connect, expected 1, but got 1, missing port");
+
+    let error = definition.instantiate(&shell, vec![literal("example.com"), flag("verbose")]).err().unwrap(); // port would be a gap
+    assert_eq!(format!("{}", error), "Too few arguments for connect, expected 1, but got 2, missing port");
+
+    // Only a whole unquoted token is a flag, so the values can hold dashes
+    let (_, args) = read::get_tokens("print \"use --force\" a--b \"--verbose\" --verbose").unwrap();
+    assert_eq!(args.iter().map(|arg| (arg.to_string(), matches!(arg, ArgToken::Flag(_, _)))).collect::<Vec<_>>(), vec![
+        ("use --force".to_string(), false), ("a--b".to_string(), false), ("--verbose".to_string(), false), ("verbose".to_string(), true)
+    ]);
+
+    let print = CommandDefinition::new("print").add_named_arg("text", ArgType::Str, "The text").build();
+    for line in ["print \"use --force\"", "print a--b", "print \"a--b\""] {
+        let (_, args) = read::get_tokens(line).unwrap();
+        assert!(print.instantiate(&shell, args).is_ok(), "{} should be a value", line);
+    }
+
+    assert!(matches!(read::get_tokens("print-it now"), Err(InputError::NotACommand(_, _)))); // The whole name must be an identifier
+}
+
+#[test]
+fn suggestions_test() {
+    assert_eq!(suggest::edit_distance("statsu", "status"), 1);
+    assert_eq!(suggest::edit_distance("kitten", "sitting"), 3);
+    assert_eq!(suggest::edit_distance("", "help"), 4);
+
+    assert_eq!(suggest::suggestions("hepl", ["help", "history", "exit"]), vec!["help"]);
+    assert!(suggest::suggestions("deploy", ["help", "history", "exit"]).is_empty());
+
+    let error = ArgType::Enum(vec!["status", "stats", "stop"]).evaluate(&literal("statsu")).unwrap_err();
+    match &error {
+        CommandError::MismatchArgument { suggestions, .. } => assert_eq!(suggestions, &vec!["stats", "status"]),
+        _ => panic!("Expected a MismatchArgument, got {}", error)
+    }
+    assert!(format!("{}", error).ends_with(", did you mean stats or status?"));
+}
+
+#[test]
+fn spans_test() {
+    let (identifier, args) = read::get_tokens("sum  1 \"two three\"").unwrap();
+
+    assert_eq!(identifier.1, Span::new(0, 3, 0, 3));
+    assert_eq!(args[0].span(), Span::new(5, 6, 5, 6));
+    assert_eq!(args[1].span(), Span::new(7, 18, 7, 18));
+    assert_eq!(args[1].to_string(), "two three");
+
+    let shell = Shell::new();
+    let definition = CommandDefinition::new("sum")
+        .add_named_arg("a", ArgType::Int, "The first term")
+        .add_named_arg("b", ArgType::Int, "The second term")
+        .build();
+
+    let line = "sum 1 two";
+    let (_, args) = read::get_tokens(line).unwrap();
+    let error = definition.instantiate(&shell, args).err().unwrap();
+
+    assert_eq!(read::caret_diagnostic(line, error.span().unwrap()), "sum 1 two\n      ^^^");
+
+    let error = read::get_tokens("  42 1 2").unwrap_err();
+    assert_eq!(error.span(), Some(Span::new(2, 4, 2, 4)));
+}
+
+#[test]
+fn error_codes_test() {
+    let shell = Shell::new();
+
+    let error: ShellError = shell.get_env_var::<i32>("MISSING").unwrap_err().into();
+    assert_eq!(error.code(), "E300");
+    assert!(matches!(error, ShellError::EnvVar(EnvVarError::Unset(_))));
+
+    let error: ShellError = read::get_tokens("42").unwrap_err().into();
+    assert_eq!(error.code(), "E101");
+    assert!(matches!(error, ShellError::Input(InputError::NotACommand(_, _))));
+    assert_eq!(error.to_string(), "42 isn't a command");
+    assert!(std::error::Error::source(&error).is_none()); // Transparent, the message isn't repeated by error reporters
+
+    let definition = CommandDefinition::new("sum").add_arg(ArgType::Int).build();
+    let (_, args) = read::get_tokens("sum 1 2").unwrap();
+    let error: ShellError = definition.instantiate(&shell, args).err().unwrap().into();
+    assert_eq!(error.code(), "E202");
+    assert_eq!(error.span(), Some(Span::new(6, 7, 6, 7)));
+}
+
+#[test]
+fn log_levels_test() {
+    assert!(LogLevel::TRACE < LogLevel::DEBUG && LogLevel::DEBUG < LogLevel::INFO && LogLevel::WARN < LogLevel::ERROR);
+    assert_eq!("Debug".parse::<LogLevel>().unwrap(), LogLevel::DEBUG);
+    assert!("loud".parse::<LogLevel>().is_err());
+
+    let directory = format!("{}/", temp_path("log-levels-test"));
+    let _ = fs::remove_dir_all(&directory);
+
+    let mut shell = Shell::new();
+    shell
+        .set_log_directory(&directory).unwrap()
+        .set_terminal_log_level(LogLevel::ERROR);
+
+    shell.log(LogLevel::DEBUG, "hidden");
+    shell.log(LogLevel::INFO, "shown");
+
+    run_lines(&mut shell, "$LOG_FILE_LEVEL=debug\n$LOG_LEVEL=loud\n");
+
+    assert_eq!(shell.file_log_level(), LogLevel::DEBUG);
+    assert_eq!(shell.terminal_log_level(), LogLevel::ERROR);
+
+    shell.log(LogLevel::DEBUG, "debugging");
+    shell.log(LogLevel::TRACE, "tracing");
+
+    let content = fs::read_to_string(shell.log_path().unwrap()).unwrap();
+
+    assert!(!content.contains("hidden") && !content.contains("tracing"));
+    assert!(content.contains(&format!("{} [INFO] shown", shell.session_id())));
+    assert!(content.contains("[DEBUG] debugging"));
+    assert!(content.contains("[WARN] loud isn't a log level"));
+}
+
+#[test]
+fn log_rotation_test() {
+    let directory = temp_path("log-rotation-test"); // No trailing slash
+    let _ = fs::remove_dir_all(&directory);
+
+    let mut shell = Shell::new();
+    shell
+        .set_log_directory(&directory).unwrap()
+        .set_log_pattern("app-%Y:%m:%d.log").unwrap()
+        .set_log_rotation(Rotation::Size(1))
+        .set_log_retention(Retention::Count(2))
+        .set_terminal_log_level(LogLevel::ERROR);
+
+    shell.log(LogLevel::INFO, "first");
+    let first = shell.log_path().unwrap();
+
+    assert_eq!(first.parent().unwrap(), std::path::Path::new(&directory));
+    assert_eq!(first.file_name().unwrap().to_string_lossy(), Local::now().format("app-%Y-%m-%d.log").to_string());
+
+    shell.log(LogLevel::INFO, "second");
+    shell.log(LogLevel::INFO, "third");
+
+    assert_eq!(shell.log_path().unwrap(), std::path::PathBuf::from(format!("{}.2", first.display())));
+
+    let mut names: Vec<String> = fs::read_dir(&directory).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+
+    assert_eq!(names.len(), 2);
+    assert!(!names.contains(&first.file_name().unwrap().to_string_lossy().to_string()));
+}
+
+#[test]
+fn log_retention_test() {
+    let directory = temp_path("log-retention-test");
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    let old = Local::now().format("%Y").to_string().parse::<i32>().unwrap() - 1;
+    let unrelated = ["notes.log", "100%.log", "2023.log.bak", "20230.log"];
+
+    for name in unrelated.iter().map(|name| name.to_string()).chain([format!("{}.log", old), format!("{}.log.3", old)]) {
+        fs::write(format!("{}/{}", directory, name), "").unwrap();
+    }
+
+    let mut shell = Shell::new();
+    shell
+        .set_log_directory(&directory).unwrap()
+        .set_log_pattern("%Y.log").unwrap()
+        .set_log_retention(Retention::Count(1))
+        .set_terminal_log_level(LogLevel::ERROR);
+
+    shell.log(LogLevel::INFO, "first");
+
+    let mut names: Vec<String> = fs::read_dir(&directory).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+
+    let mut expected: Vec<String> = unrelated.iter().map(|name| name.to_string()).collect();
+    expected.push(Local::now().format("%Y.log").to_string());
+    expected.sort();
+
+    assert_eq!(names, expected); // Only the old logs of the pattern were deleted
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn log_facade_test() {
+    let directory = temp_path("log-facade-test");
+    let _ = fs::remove_dir_all(&directory);
+
+    let mut shell = Shell::new();
+    shell
+        .set_log_directory(&directory).unwrap()
+        .set_terminal_log_level(LogLevel::ERROR)
+        .set_file_log_level(LogLevel::DEBUG);
+    shell.install_logger().unwrap();
+
+    ::log::debug!(target: "app", "from the facade");
+    ::log::trace!("below the threshold");
+    shell.log(LogLevel::INFO, "from the shell");
+
+    let content = fs::read_to_string(shell.log_path().unwrap()).unwrap();
+
+    assert!(content.contains("[DEBUG] app: from the facade"));
+    assert!(content.contains("[INFO] from the shell"));
+    assert!(!content.contains("below the threshold"));
+}
+
+#[test]
+fn json_log_test() {
+    let directory = temp_path("json-log-test");
+    let _ = fs::remove_dir_all(&directory);
+
+    let mut shell = Shell::new();
+    shell
+        .set_log_directory(&directory).unwrap()
+        .set_log_format(LogFormat::JsonLines)
+        .set_terminal_log_level(LogLevel::ERROR)
+        .register_command(
+            CommandDefinition::new("greet")
+            .add_arg(ArgType::Str)
+            .add_arg(ArgType::Int)
+            .set_callback(|shell, args| shell.log(LogLevel::WARN, &format!("Hello \"{}\" {} times", args[0].get_str().unwrap(), args[1].get_int().unwrap())))
+            .build()
+        );
+
+    run_lines(&mut shell, "greet \"big world\" +16\n");
+
+    let content = fs::read_to_string(shell.log_path().unwrap()).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    let session = format!("\"session_id\":\"{}\"", shell.session_id());
+
+    assert_eq!(lines.len(), 3); // The line, what its command logs and how it went
+    assert!(lines.iter().all(|line| line.starts_with("{\"timestamp\":\"") && line.ends_with('}')));
+    assert!(lines[0].contains(&format!("\"level\":\"INFO\",{},\"message\":\">> greet \\\"big world\\\" +16\"}}", session)));
+    assert!(lines[1].contains(&format!("\"level\":\"WARN\",{},\"message\":\"Hello \\\"big world\\\" 16 times\"}}", session)));
+    assert!(lines[2].contains("\"message\":\"<< greet\",\"command\":\"greet\",\"args\":[\"big world\",\"16\"],\"status\":0,\"duration_ms\":")); // The parsed values
+}
+
+#[test]
+fn fallible_config_test() {
+    let base = temp_path("fallible-config-test");
+    let _ = fs::remove_dir_all(&base);
+    fs::create_dir_all(&base).unwrap();
+
+    let blocker = format!("{}/blocker", base); // A file where a directory is expected
+    fs::write(&blocker, "").unwrap();
+
+    let mut shell = Shell::new();
+    shell.set_terminal_log_level(LogLevel::ERROR);
+
+    let error = shell.set_log_directory(&format!("{}/logs", blocker)).err().unwrap();
+    assert!(matches!(error, ConfigError::LogDirectory(_, _)));
+    let error = ShellError::from(error);
+    assert_eq!(error.code(), "E400");
+    assert!(std::error::Error::source(&error).unwrap().is::<std::io::Error>());
+    assert!(matches!(shell.set_log_pattern("app-%Q.log"), Err(ConfigError::LogPattern(_))));
+
+    // The log file becomes unwritable after it was created, so the entries move to the fallback directory
+    let logs = format!("{}/logs", base);
+    let fallback = format!("{}/fallback", base);
+
+    shell
+        .set_log_directory(&logs).unwrap()
+        .set_log_fallback(LogFallback::Directory(fallback.clone().into()));
+
+    shell.log(LogLevel::INFO, "first");
+    let first = shell.log_path().unwrap();
+
+    fs::remove_file(&first).unwrap();
+    fs::create_dir(&first).unwrap();
+
+    shell.log(LogLevel::INFO, "second");
+
+    let moved = shell.log_path().unwrap();
+    assert_eq!(moved.parent().unwrap(), std::path::Path::new(&fallback));
+    assert!(fs::read_to_string(moved).unwrap().contains("[INFO] second"));
+
+    // Without a fallback the file logging just stops
+    fs::remove_dir_all(&fallback).unwrap();
+    fs::write(&fallback, "").unwrap();
+
+    shell.log(LogLevel::INFO, "third");
+    shell.log(LogLevel::INFO, "fourth");
+
+    assert_eq!(shell.log_path(), None);
+}
+
+#[test]
+fn run_and_exit_test() {
+    let path = &temp_file("run-and-exit-test.rc", "$BEFORE=yes\nexit 3\n$AFTER=yes\n");
+
+    let exited = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(-1));
+    let hook_exited = exited.clone();
+
+    let mut shell = Shell::new();
+    shell
+        .set_rc_file(path)
+        .register_exit()
+        .on_exit(move |shell, code| hook_exited.store(code + shell.history_len() as i32, std::sync::atomic::Ordering::SeqCst));
+
+    assert_eq!(shell.run(), 3); // The rc file exits before anything is read from stdin
+    assert_eq!(exited.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert_eq!(shell.get_env_var::<String>("BEFORE").unwrap(), "yes");
+    assert!(shell.get_env_var::<String>("AFTER").is_err());
+    assert_eq!(shell.exit_code(), None);
+
+    shell.exit_with(5);
+    assert_eq!(shell.exit_code(), Some(5));
+}
+
+#[test]
+fn interrupt_test() {
+    use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+
+    let started = Arc::new(AtomicBool::new(false));
+    let callback_started = started.clone();
+
+    let mut shell = Shell::new();
+    shell
+        .set_interrupt_policy(InterruptPolicy::Ignore)
+        .register_command(
+            CommandDefinition::new("wait")
+            .set_callback(move |shell, _args| {
+                callback_started.store(true, Ordering::SeqCst);
+
+                let token = shell.cancellation_token();
+                let deadline = std::time::Instant::now() + Duration::from_secs(5);
+
+                while !token.is_cancelled() && std::time::Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            })
+            .build()
+        );
+
+    let handle = shell.interrupt_handle();
+    let interrupter = std::thread::spawn(move || {
+        while !started.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        handle.interrupt();
+        handle.interrupt(); // Ignored by the policy instead of ending the process
+    });
+
+    run_lines(&mut shell, "wait\n");
+    interrupter.join().unwrap();
+
+    assert!(shell.is_cancelled());
+    assert_eq!(shell.last_status(), 130);
+
+    shell
+        .register_jobs()
+        .register_command(CommandDefinition::new("stop").set_callback(|shell, _args| shell.interrupt_handle().interrupt()).build())
+        .register_command(CommandDefinition::new("noop").set_callback(|_shell, _args| {}).build());
+
+    run_lines(&mut shell, "$X=1\n"); // The lines after a cancelled command don't inherit its status
+    assert_eq!(shell.last_status(), 0);
+
+    run_lines(&mut shell, "stop\n");
+    assert_eq!(shell.last_status(), 130);
+    run_lines(&mut shell, "noop &\n");
+    assert_eq!(shell.last_status(), 0);
+    shell.wait_jobs(None);
+
+    let token = CancellationToken::new();
+    let clone = token.clone();
+    clone.cancel();
+    assert!(token.is_cancelled());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_callback_test() {
+    use std::sync::{Arc, atomic::{AtomicI64, Ordering}};
+
+    let total = Arc::new(AtomicI64::new(0));
+    let callback_total = total.clone();
+
+    let mut shell = Shell::new();
+    shell.register_command(
+        CommandDefinition::new("add")
+        .add_arg(ArgType::Int)
+        .set_async_callback(move |_shell, args| {
+            let total = callback_total.clone();
+
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                total.fetch_add(args[0].get_int().unwrap() as i64, Ordering::SeqCst);
+            })
+        })
+        .build()
+    );
+
+    run_lines(&mut shell, "add 2\nadd 40\n"); // Without run_async each callback gets a runtime of its own
+
+    assert_eq!(total.load(Ordering::SeqCst), 42);
+
+    fn assert_send<T: Send>(_: &T) {}
+    let run = shell.run_async();
+    assert_send(&run); // So it can be given to tokio::spawn
+    drop(run);
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+    runtime.block_on(async {
+        run_lines(&mut shell, "add 1\n"); // It can't start a runtime inside this one, so the line fails instead of panicking
+        assert_eq!(shell.last_status(), 1);
+
+        shell.source_async(&temp_file("async-callback-test.rc", "add 100\n")).await.unwrap();
+        assert_eq!(shell.last_status(), 0);
+
+        shell.register_exit().set_rc_file(&temp_file("async-callback-test-exit.rc", "add 1000\nexit 7\n"));
+        assert_eq!(shell.run_async().await, 7); // The rc file is awaited too
+    });
+
+    assert_eq!(total.load(Ordering::SeqCst), 1142);
+
+    let token = CancellationToken::new();
+    let cancelling = token.clone();
+
+    runtime.block_on(async move {
+        std::thread::spawn(move || cancelling.cancel());
+        token.cancelled().await;
+    });
+}
+
+#[test]
+fn jobs_test() {
+    let mut shell = Shell::new();
+    shell
+        .set_terminal_log_level(LogLevel::ERROR)
+        .register_jobs()
+        .register_command(
+            CommandDefinition::new("greet")
+            .add_arg(ArgType::Str)
+            .set_callback(|shell, args| shell.println(&format!("Hello {}", args[0].get_str().unwrap())))
+            .build()
+        )
+        .register_command(
+            CommandDefinition::new("crash")
+            .set_callback(|_shell, _args| panic!("The job panics on purpose"))
+            .build()
+        )
+        .register_command(
+            CommandDefinition::new("fail")
+            .set_callback(|shell, _args| { shell.log(LogLevel::ERROR, "Something broke"); shell.set_status(2); })
+            .build()
+        )
+        .register_command(
+            CommandDefinition::new("spin")
+            .set_callback(|shell, _args| {
+                let deadline = std::time::Instant::now() + Duration::from_secs(5);
+
+                while !shell.is_cancelled() && std::time::Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            })
+            .build()
+        );
+
+    run_lines(&mut shell, "greet world &\nspin &\n");
+
+    shell.wait_jobs(Some(1));
+    assert_eq!(shell.job_status(1), Some(JobStatus::Done(0)));
+    assert_eq!(shell.job_output(1).unwrap(), "Hello world\n");
+
+    assert!(shell.kill_job(2));
+    shell.wait_jobs(None);
+    assert_eq!(shell.job_status(2), Some(JobStatus::Killed));
+    assert!(!shell.kill_job(3));
+
+    run_lines(&mut shell, "greet &\n"); // The errors are reported right away instead of starting a job
+    assert_eq!(shell.last_status(), 1);
+    assert_eq!(shell.job_status(3), None);
+
+    run_lines(&mut shell, "fail &\n"); // What it logs to the terminal goes to its buffer
+    shell.wait_jobs(Some(3));
+    assert_eq!(shell.job_status(3), Some(JobStatus::Done(2)));
+    assert_eq!(shell.job_output(3).unwrap(), "[ERROR] Something broke\n");
+
+    run_lines(&mut shell, "crash &\n");
+    shell.wait_jobs(Some(4));
+    assert_eq!(shell.job_status(4), Some(JobStatus::Done(101)));
+    assert_eq!(shell.job_output(4).unwrap(), "[ERROR] The job crash panicked\n");
+
+    run_lines(&mut shell, "kill -1\n"); // Not a huge id
+    assert_eq!(shell.last_status(), 1);
+
+    run_lines(&mut shell, "spin &\n");
+    let handle = shell.interrupt_handle();
+    let interrupter = std::thread::spawn(move || { std::thread::sleep(Duration::from_millis(100)); handle.interrupt(); });
+
+    run_lines(&mut shell, "wait 5\n"); // Ctrl-C stops the waiting, the job keeps running
+    interrupter.join().unwrap();
+    assert_eq!(shell.last_status(), 130);
+    assert_eq!(shell.job_status(5), Some(JobStatus::Running));
+
+    run_lines(&mut shell, "kill 5\nwait\n");
+    assert_eq!(shell.job_status(5), Some(JobStatus::Killed));
+}
+
+#[test]
+fn external_commands_test() {
+    let mut shell = Shell::new();
+    shell
+        .set_terminal_log_level(LogLevel::ERROR)
+        .register_jobs()
+        .register_command(
+            CommandDefinition::new("true")
+            .set_callback(|shell, _args| shell.set_status(7))
+            .build()
+        );
+
+    run_lines(&mut shell, "false\n"); // Off by default
+    assert_eq!(shell.last_status(), 1);
+
+    shell.set_external_commands(true);
+
+    run_lines(&mut shell, "true\n"); // Registered commands come first
+    assert_eq!(shell.last_status(), 7);
+
+    run_lines(&mut shell, "!true\n");
+    assert_eq!(shell.last_status(), 0);
+
+    run_lines(&mut shell, "sh -c \"exit 3\"\n");
+    assert_eq!(shell.last_status(), 3);
+
+    run_lines(&mut shell, "!notaprogramanywhere\n");
+    assert_eq!(shell.last_status(), 1);
+
+    run_lines(&mut shell, "$GREETING=hello\nsh -c \"echo $GREETING --loud\" &\n"); // The variables are exported and the flags are left as they are
+    shell.wait_jobs(Some(1));
+    assert_eq!(shell.job_status(1), Some(JobStatus::Done(0)));
+    assert_eq!(shell.job_output(1).unwrap(), "hello --loud\n");
+}
+
+#[test]
+fn redirections_test() {
+    let (line, redirections) = read::get_redirections("greet \"a > b\" >> out.txt <in.csv\n").unwrap();
+    assert_eq!(line, "greet \"a > b\"                   \n");
+    assert_eq!(redirections, vec![
+        read::Redirection::Append("out.txt".to_string(), Span::new(17, 24, 17, 24)),
+        read::Redirection::Read("in.csv".to_string(), Span::new(26, 32, 26, 32))
+    ]);
+
+    let error = read::get_redirections("greet >").unwrap_err();
+    assert_eq!(error.code(), "E104");
+    assert_eq!(error.span(), Some(Span::new(6, 7, 6, 7)));
+
+    let ran = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let count_ran = ran.clone();
+
+    let mut shell = Shell::new();
+    shell
+        .set_terminal_log_level(LogLevel::ERROR)
+        .register_command(
+            CommandDefinition::new("greet")
+            .add_arg(ArgType::Str)
+            .set_callback(|shell, args| shell.println(&format!("Hello {}", args[0].get_str().unwrap())))
+            .build()
+        )
+        .register_command(
+            CommandDefinition::new("count")
+            .set_callback(move |shell, _args| {
+                count_ran.store(true, std::sync::atomic::Ordering::SeqCst);
+                let text = shell.input().read_to_string().unwrap();
+                shell.println(&text.lines().count().to_string());
+            })
+            .build()
+        );
+
+    let out = &temp_path("redirections-test.out");
+    let input = &temp_path("redirections-test.csv");
+    fs::write(input, "a,1\nb,2\nc,3\n").unwrap();
+
+    run_lines(&mut shell, &format!("greet world > {out}\ngreet again >> {out}\ncount < {input} >> {out}\n"));
+    assert_eq!(fs::read_to_string(out).unwrap(), "Hello world\nHello again\n3\n");
+    assert!(shell.output().is_terminal() && shell.input().is_terminal()); // Only for the line
+
+    ran.store(false, std::sync::atomic::Ordering::SeqCst);
+    run_lines(&mut shell, &format!("count < {}/missing.csv\n", temp_path("redirections-test"))); // The command doesn't start
+    assert_eq!(shell.last_status(), 1);
+    assert!(!ran.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[test]
+fn help_categories_test() {
+    let mut shell = Shell::new();
+    shell
+        .set_terminal_log_level(LogLevel::ERROR)
+        .register_help()
+        .register_exit()
+        .register_command(CommandDefinition::new("push").set_category("git").set_description("Uploads the commits").build())
+        .register_command(CommandDefinition::new("commit").set_category("Git").build()) // The same category as git
+        .register_command(CommandDefinition::new("deploy").set_category("Cloud").build())
+        .register_command(CommandDefinition::new("tag").set_category("other").build()) // The same as no category
+        .register_command(CommandDefinition::new("ping").build());
+
+    assert_eq!(shell.categories(), vec!["Cloud", "Git", "Shell", ""]);
+    assert!(shell.has_category("GIT") && shell.has_category("other") && !shell.has_category("push"));
+
+    let out = &temp_path("help-categories-test.out");
+
+    run_lines(&mut shell, &format!("help > {out}\n"));
+    let help = fs::read_to_string(out).unwrap(); // The description of help is wrapped to the terminal width
+    assert!(help.starts_with("Cloud:\n  deploy\n\nGit:\n  commit\n  push - Uploads the commits\n\nShell:\n  exit [code:Int] - Exits the program\n  help [name:Str] - "));
+    assert!(help.ends_with("\n\nOther:\n  ping\n  tag\n"));
+    assert_eq!(help.matches("Git:").count() + help.matches("git:").count() + help.matches("Other:").count(), 2);
+
+    run_lines(&mut shell, &format!("help git > {out}\n"));
+    assert_eq!(fs::read_to_string(out).unwrap(), "commit\npush - Uploads the commits\n");
+}
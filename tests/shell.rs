@@ -6,15 +6,15 @@ fn main() {
     shell
         .set_sparse(true)
         .set_prompt("$USER$ ~>>")
-        .set_log_directory("/tmp/diysh/")
+        .set_log_directory("/tmp/diysh/").expect("Couldn't create the log directory")
         .register_env_var("USER", "ojarrisonn_")
         .register_help()
         .register_history()
         .register_exit()
         
         .register_command( CommandDefinition::new("print")
-            .set_description("text:str - Prints the specified text to the terminal")
-            .add_arg(ArgType::Str)
+            .set_description("Prints the specified text to the terminal")
+            .add_named_arg("text", ArgType::Str, "The text to print")
             .set_callback(|shell, args| {
                 let text = args[0].get_str().unwrap();
 
@@ -24,9 +24,9 @@ fn main() {
         )
         
         .register_command( CommandDefinition::new("sum")
-            .set_description("a:int b:int - Prints the result of the sum of a + b")
-            .add_arg(ArgType::Int)
-            .add_arg(ArgType::Int)
+            .set_description("Prints the result of the sum of a + b")
+            .add_named_arg("a", ArgType::Int, "The first term")
+            .add_named_arg("b", ArgType::Int, "The second term")
             .set_callback(|shell, args| {
                 let a = args[0].get_int().unwrap();
                 let b = args[1].get_int().unwrap();
@@ -37,7 +37,7 @@ fn main() {
         )
         
         .register_command(CommandDefinition::new("echoEnv")
-            .set_description("- Prints the value stored in $TO_PRINT")
+            .set_description("Prints the value stored in $TO_PRINT")
             .set_callback(|shell, _args| {
                 match shell.get_env_var::<String>("TO_PRINT") {
                     Ok(text) => shell.log(LogLevel::INFO, &text),
@@ -48,7 +48,5 @@ fn main() {
     
         );
 
-    loop {
-        shell.read_and_run();
-    }
+    std::process::exit(shell.run());
 }
\ No newline at end of file